@@ -0,0 +1,55 @@
+//! Compare the nodes per second of the bitboard move generator against the
+//! original one, by counting the leaves of the move tree of a few positions.
+//!
+//! Run with `cargo bench`.
+extern crate chess_engine;
+use chess_engine::*;
+use std::time::Instant;
+
+const POSITIONS: [(&str, &str, u32); 3] = [
+    (
+        "start",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        4,
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        3,
+    ),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+];
+
+fn count_nodes(board: &Board, depth: u32, generate: fn(&Board) -> Vec<Move>) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    generate(board)
+        .into_iter()
+        .map(|m| count_nodes(&board.apply_eval_move(m), depth - 1, generate))
+        .sum()
+}
+
+fn bench(name: &str, board: &Board, depth: u32, generate: fn(&Board) -> Vec<Move>) -> f64 {
+    let start = Instant::now();
+    let nodes = count_nodes(board, depth, generate);
+    let elapsed = start.elapsed().as_secs_f64();
+    let nps = nodes as f64 / elapsed;
+    println!(
+        "  {:<9} {:>10} nodes in {:>8.3}s = {:>12.0} nodes/s",
+        name, nodes, elapsed, nps
+    );
+    nps
+}
+
+fn main() {
+    for (name, fen, depth) in POSITIONS {
+        let board = parse_fen(fen).unwrap();
+        println!("{} (depth {})", name, depth);
+        let bitboard = bench("bitboard", &board, depth, |board| board.get_legal_moves());
+        let mailbox = bench("mailbox", &board, depth, |board| {
+            board.get_legal_moves_mailbox()
+        });
+        println!("  speedup   {:.1}x", bitboard / mailbox);
+    }
+}
@@ -2,7 +2,9 @@ extern crate chess_engine;
 use chess_engine::*;
 use std::{
     convert::TryFrom,
+    env, fs,
     io::{stdin, stdout, Write},
+    time::{Duration, Instant},
 };
 
 fn input(prompt: impl std::fmt::Display) -> String {
@@ -15,14 +17,48 @@ fn input(prompt: impl std::fmt::Display) -> String {
     s
 }
 
-fn get_cpu_move(b: &Board, best: bool) -> Move {
-    let (m, count, _) = if best {
-        b.get_best_next_move(4)
+/// How long the CPU thinks about its best move.
+const CPU_THINKING_TIME: Duration = Duration::from_secs(2);
+
+/// Write a line of moves from the given board in SAN, numbered like a game
+/// record.
+fn format_pv(board: &Board, pv: &[Move]) -> String {
+    let mut board = *board;
+    let mut moves = Vec::new();
+    for (i, m) in pv.iter().enumerate() {
+        match board.get_turn_color() {
+            WHITE => moves.push(format!("{}.", board.get_fullmove_number())),
+            BLACK if i == 0 => moves.push(format!("{}...", board.get_fullmove_number())),
+            BLACK => {}
+        }
+        moves.push(to_san(&board, *m));
+        board = board.apply_eval_move(*m);
+    }
+    moves.join(" ")
+}
+
+fn get_cpu_move(b: &Board, best: bool, params: &EvalParams, table: &mut TranspositionTable) -> Move {
+    let mut result = None;
+    let m = if best {
+        let limits = SearchLimits {
+            movetime: Some(CPU_THINKING_TIME.as_millis() as u64),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let best = b
+            .with_evaluator(params)
+            .search(&limits, table, &mut || start.elapsed() >= CPU_THINKING_TIME);
+        print!(
+            "CPU evaluated {} moves ({} found in the table) to depth {} before choosing to ",
+            best.stats.board_count, best.stats.tt_hits, best.depth
+        );
+        result.insert(best).best_move
     } else {
-        b.get_worst_next_move(4)
+        let (m, count, _) = b.with_evaluator(params).get_worst_next_move(4);
+        print!("CPU evaluated {} moves before choosing to ", count);
+        m
     };
 
-    print!("CPU evaluated {} moves before choosing to ", count);
     match m {
         Move::Piece(from, to) | Move::Promotion(from, to, _) => {
             match (b.get_piece(from), b.get_piece(to)) {
@@ -47,15 +83,159 @@ fn get_cpu_move(b: &Board, best: bool) -> Move {
         }
         Move::Resign => println!("resign"),
     }
+    if let Some(result) = result.filter(|result| !result.pv.is_empty()) {
+        println!(
+            "Expected line: {} (score {}, depth {}/{})",
+            format_pv(b, &result.pv),
+            result.value,
+            result.depth,
+            result.stats.seldepth
+        );
+    }
 
     m
 }
 
+/// Run `analyze <lines>`, printing the best moves the CPU finds with their
+/// scores and expected lines of play.
+fn analyze(b: &Board, args: &str, params: &EvalParams, table: &mut TranspositionTable) {
+    let lines = match args.parse::<usize>() {
+        Ok(lines) if lines > 0 => lines,
+        _ => {
+            eprintln!("usage: analyze <number of moves>");
+            return;
+        }
+    };
+    let limits = SearchLimits {
+        movetime: Some(CPU_THINKING_TIME.as_millis() as u64),
+        ..SearchLimits::default()
+    };
+    let start = Instant::now();
+    let results = b.with_evaluator(params).search_multipv(
+        &limits,
+        SearchConfig::default(),
+        lines,
+        table,
+        &mut || start.elapsed() >= CPU_THINKING_TIME,
+    );
+
+    if results[0].best_move == Move::Resign {
+        println!("There are no legal moves.");
+        return;
+    }
+    println!("Best moves at depth {}:", results[0].depth);
+    for (i, result) in results.iter().enumerate() {
+        println!("{:>3}. {:>6}  {}", i + 1, result.value.to_string(), format_pv(b, &result.pv));
+    }
+}
+
+/// Print the evaluation of the position term by term, for each side in
+/// the middlegame and the endgame, in pawns.
+fn print_eval(b: &Board, params: &EvalParams) {
+    let trace = b.evaluate_trace_with_params(b.get_turn_color(), params);
+    let pawns = |term: EvalTerm| format!("{:>7.2}{:>7.2}", term.middlegame / 10.0, term.endgame / 10.0);
+    println!("{:<14}{:^14}  {:^14}  {:^14}", "Term", "White", "Black", "Total");
+    println!("{:<14}{:>7}{:>7}  {:>7}{:>7}  {:>7}{:>7}", "", "MG", "EG", "MG", "EG", "MG", "EG");
+    for (name, term) in trace.terms() {
+        println!("{:<14}{}  {}  {}", name, pawns(term.white), pawns(term.black), pawns(term.get_net()));
+    }
+    println!("{:<14}{:>32}{}", "Total", "", pawns(trace.total()));
+    println!(
+        "Phase {}/{}, so the position is worth {} to {}",
+        trace.phase,
+        MAX_PHASE,
+        Score::from_value(trace.value()),
+        trace.color
+    );
+}
+
+fn print_history(game: &Game) {
+    for (i, pair) in game.history().chunks(2).enumerate() {
+        match pair {
+            [white, black] => println!("{}. {} {}", i + 1, white.san, black.san),
+            [white] => println!("{}. {}", i + 1, white.san),
+            _ => {}
+        }
+    }
+}
+
+fn print_game_over(status: &GameOver) {
+    match status {
+        GameOver::WhiteCheckmates | GameOver::BlackResigns => {
+            println!("{} loses. {} is victorious.", BLACK, WHITE)
+        }
+        GameOver::BlackCheckmates | GameOver::WhiteResigns => {
+            println!("{} loses. {} is victorious.", WHITE, BLACK)
+        }
+        GameOver::ThreefoldRepetition | GameOver::FivefoldRepetition => {
+            println!("Drawn game by repetition.")
+        }
+        GameOver::FiftyMoveRule | GameOver::SeventyFiveMoveRule => {
+            println!("Drawn game by the fifty-move rule.")
+        }
+        GameOver::Stalemate | GameOver::DrawAccepted => println!("Drawn game."),
+    }
+}
+
+/// Run `chess perft <depth> [fen]`, printing the node count below each
+/// move like `divide`, then the total.
+fn perft(args: &[String]) -> Result<(), String> {
+    let depth = match args.first().map(|depth| depth.parse::<u32>()) {
+        Some(Ok(depth)) => depth,
+        _ => return Err(String::from("usage: chess perft <depth> [fen]")),
+    };
+    let board = match args.get(1..) {
+        Some(fen) if !fen.is_empty() => parse_fen(&fen.join(" "))?,
+        _ => Board::default(),
+    };
+
+    let start = Instant::now();
+    let mut total = 0;
+    for (m, nodes) in board.divide(depth) {
+        println!("{}: {}", m.to_uci(&board), nodes);
+        total += nodes;
+    }
+    let elapsed = start.elapsed();
+
+    println!();
+    println!("Nodes searched: {}", total);
+    println!(
+        "Time: {}ms ({} nodes/s)",
+        elapsed.as_millis(),
+        (total as f64 / elapsed.as_secs_f64().max(0.001)) as u64
+    );
+    Ok(())
+}
+
+/// Read the evaluation parameters for `chess --params <file>`.
+fn load_params(args: &[String]) -> Result<EvalParams, String> {
+    match args {
+        [] => Ok(EvalParams::default()),
+        [flag, path] if flag == "--params" => {
+            let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            EvalParams::parse(&text).map_err(|e| format!("{}: {}", path, e))
+        }
+        _ => Err(String::from("usage: chess [--params <file>]")),
+    }
+}
+
 fn main() -> Result<(), String> {
-    let mut b = Board::default();
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    match args.first().map(String::as_str) {
+        Some("perft") => return perft(&args[1..]),
+        // write out the defaults, to start a parameter file from
+        Some("params") => {
+            print!("{}", EvalParams::default());
+            return Ok(());
+        }
+        _ => {}
+    }
+    let params = load_params(&args)?;
+
+    let mut game = Game::default();
+    let mut table = TranspositionTable::default();
 
-    println!("{}", b);
-    let mut history = vec![];
+    println!("{}", game.board);
 
     loop {
         let mut s = input(">>> ");
@@ -63,22 +243,44 @@ fn main() -> Result<(), String> {
 
         let m = if s.is_empty() {
             println!("Waiting for CPU to choose best move...");
-            get_cpu_move(&b, true)
+            get_cpu_move(&game.board, true, &params, &mut table)
         } else if s == "worst" {
             println!("Waiting for CPU to choose worst move...");
-            get_cpu_move(&b, false)
+            get_cpu_move(&game.board, false, &params, &mut table)
+        } else if let Some(args) = s.strip_prefix("analyze") {
+            analyze(&game.board, args.trim(), &params, &mut table);
+            continue;
+        } else if s == "eval" {
+            print_eval(&game.board, &params);
+            continue;
         } else if s == "rate" {
             continue;
         } else if s == "pass" {
-            b = b.change_turn();
+            if game.make_move(&GameAction::Pass).is_err() {
+                eprintln!("You can't pass while in check.");
+            }
             continue;
-        } else if s == "history" {
-            for i in 0..history.len() {
-                if i < history.len() - 1 {
-                    println!("{} {}", history[i], history[i + 1]);
-                } else {
-                    println!("{}", history[i]);
+        } else if s == "claim" {
+            match game.make_move(&GameAction::ClaimDraw) {
+                Ok(Some(status)) => {
+                    print_game_over(status);
+                    break;
                 }
+                _ => eprintln!("There is no draw to claim."),
+            }
+            continue;
+        } else if s == "history" {
+            print_history(&game);
+            continue;
+        } else if s == "undo" || s == "redo" {
+            let result = if s == "undo" {
+                game.undo()
+            } else {
+                game.redo().map(|_| ())
+            };
+            match result {
+                Ok(()) => println!("{}", game.board),
+                Err(_) => eprintln!("There is no move to {}.", s),
             }
             continue;
         } else {
@@ -91,32 +293,27 @@ fn main() -> Result<(), String> {
             }
         };
 
-        match b.play_move(m) {
-            GameResult::Continuing(next_board) => {
-                b = next_board;
-                println!("{}", b);
-                history.push(m);
-            }
-
-            GameResult::Victory(winner) => {
-                println!("{}", b);
-                println!("{} loses. {} is victorious.", !winner, winner);
-                break;
-            }
-
+        let action = match game.board.play_move(m) {
             GameResult::IllegalMove(x) => {
                 eprintln!("{} is an illegal move.", x);
+                continue;
             }
+            _ if m == Move::Resign => GameAction::Resign,
+            _ => GameAction::MakeMove(to_san(&game.board, m)),
+        };
 
-            GameResult::Stalemate => {
-                println!("Drawn game.");
-                break;
-            }
+        if game.make_move(&action).is_err() {
+            eprintln!("{} is an illegal move.", m);
+            continue;
         }
-    }
 
-    for m in history {
-        println!("{}", m);
+        println!("{}", game.board);
+        if let Some(status) = &game.status {
+            print_game_over(status);
+            break;
+        }
     }
+
+    print_history(&game);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,472 @@
+use super::{Board, Color, Move, Piece, Position, BLACK, WHITE};
+use alloc::vec::Vec;
+
+/// A set of squares packed into a `u64`, where bit `row * 8 + col` stands
+/// for the square at that row and column.
+pub(crate) type Bitboard = u64;
+
+const RANK_1: Bitboard = 0xFF;
+
+#[inline]
+pub(crate) fn square_index(pos: Position) -> usize { (pos.get_row() * 8 + pos.get_col()) as usize }
+
+#[inline]
+pub(crate) fn square_bit(pos: Position) -> Bitboard { 1 << square_index(pos) }
+
+#[inline]
+fn index_pos(index: u32) -> Position { Position::new(index as i32 / 8, index as i32 % 8) }
+
+#[inline]
+fn color_index(color: Color) -> usize {
+    match color {
+        WHITE => 0,
+        BLACK => 1,
+    }
+}
+
+/// The squares in a bitboard, from a1 to h8.
+pub(crate) struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = Position;
+
+    #[inline]
+    fn next(&mut self) -> Option<Position> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Some(index_pos(index))
+    }
+}
+
+#[inline]
+pub(crate) fn squares(bitboard: Bitboard) -> Squares { Squares(bitboard) }
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1, -2),
+    (2, -1),
+    (-1, -2),
+    (-2, -1),
+    (1, 2),
+    (2, 1),
+    (-1, 2),
+    (-2, 1),
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (0, -1),
+    (0, 1),
+    (1, 0),
+    (-1, 0),
+    (1, -1),
+    (-1, -1),
+    (1, 1),
+    (-1, 1),
+];
+
+/// The row and column steps of each sliding direction. The first four
+/// move towards higher square indices, the last four towards lower ones.
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (0, 1),
+    (1, 1),
+    (1, -1),
+    (-1, 0),
+    (0, -1),
+    (-1, -1),
+    (-1, 1),
+];
+
+const NORTH: usize = 0;
+const EAST: usize = 1;
+const NORTH_EAST: usize = 2;
+const NORTH_WEST: usize = 3;
+const SOUTH: usize = 4;
+const WEST: usize = 5;
+const SOUTH_WEST: usize = 6;
+const SOUTH_EAST: usize = 7;
+
+const fn offset_attacks(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut result = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let (row, col) = (index as i32 / 8, index as i32 % 8);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (to_row, to_col) = (row + offsets[i].0, col + offsets[i].1);
+            if to_row >= 0 && to_row < 8 && to_col >= 0 && to_col < 8 {
+                result[index] |= 1 << (to_row * 8 + to_col);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    result
+}
+
+const fn rays() -> [[Bitboard; 64]; 8] {
+    let mut result = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let (row_step, col_step) = DIRECTIONS[direction];
+        let mut index = 0;
+        while index < 64 {
+            let (mut row, mut col) = (index as i32 / 8 + row_step, index as i32 % 8 + col_step);
+            while row >= 0 && row < 8 && col >= 0 && col < 8 {
+                result[direction][index] |= 1 << (row * 8 + col);
+                row += row_step;
+                col += col_step;
+            }
+            index += 1;
+        }
+        direction += 1;
+    }
+    result
+}
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = offset_attacks(&KNIGHT_OFFSETS);
+static KING_ATTACKS: [Bitboard; 64] = offset_attacks(&KING_OFFSETS);
+/// The squares attacked by a pawn of each color.
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = [offset_attacks(&[(1, -1), (1, 1)]), offset_attacks(&[(-1, -1), (-1, 1)])];
+static RAYS: [[Bitboard; 64]; 8] = rays();
+
+/// The squares a slider on `index` reaches in one direction, up to and
+/// including the first occupied square.
+#[inline]
+fn ray_attacks(direction: usize, index: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][index];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let blocker = if direction < SOUTH {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[direction][blocker as usize]
+}
+
+#[inline]
+fn file_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    ray_attacks(NORTH, index, occupied) | ray_attacks(SOUTH, index, occupied)
+}
+
+#[inline]
+fn rank_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    ray_attacks(EAST, index, occupied) | ray_attacks(WEST, index, occupied)
+}
+
+#[inline]
+fn diagonal_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    ray_attacks(NORTH_EAST, index, occupied)
+        | ray_attacks(NORTH_WEST, index, occupied)
+        | ray_attacks(SOUTH_EAST, index, occupied)
+        | ray_attacks(SOUTH_WEST, index, occupied)
+}
+
+/// The squares a piece attacks from where it stands, whether or not it
+/// could legally move there.
+pub(crate) fn piece_attacks(piece: Piece, occupied: Bitboard) -> Bitboard {
+    let index = square_index(piece.get_pos());
+    match piece {
+        Piece::King(_, _) => KING_ATTACKS[index],
+        Piece::Queen(_, _) => {
+            file_attacks(index, occupied) | rank_attacks(index, occupied) | diagonal_attacks(index, occupied)
+        }
+        Piece::Rook(_, _) => file_attacks(index, occupied) | rank_attacks(index, occupied),
+        Piece::Bishop(_, _) => diagonal_attacks(index, occupied),
+        Piece::Knight(_, _) => KNIGHT_ATTACKS[index],
+        Piece::Pawn(color, _) => PAWN_ATTACKS[color_index(color)][index],
+    }
+}
+
+/// The squares holding each kind and color of piece.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Bitboards {
+    /// Indexed by `Piece::kind_index`.
+    pieces: [Bitboard; 6],
+    colors: [Bitboard; 2],
+}
+
+impl Bitboards {
+    #[inline]
+    pub(crate) fn toggle(&mut self, piece: Piece) {
+        let bit = square_bit(piece.get_pos());
+        self.pieces[piece.kind_index()] ^= bit;
+        self.colors[color_index(piece.get_color())] ^= bit;
+    }
+
+    #[inline]
+    pub(crate) fn occupied(&self) -> Bitboard { self.colors[0] | self.colors[1] }
+
+    #[inline]
+    pub(crate) fn color(&self, color: Color) -> Bitboard { self.colors[color_index(color)] }
+
+    #[inline]
+    pub(crate) fn kings(&self, color: Color) -> Bitboard { self.pieces[0] & self.color(color) }
+
+    #[inline]
+    pub(crate) fn pawns(&self, color: Color) -> Bitboard { self.pieces[5] & self.color(color) }
+
+    /// The squares holding the color's pieces, besides its king and pawns.
+    #[inline]
+    pub(crate) fn non_pawn_material(&self, color: Color) -> Bitboard {
+        self.color(color) & !(self.pieces[0] | self.pieces[5])
+    }
+
+    /// Is the square attacked by any of `attackers`, a subset of the
+    /// squares holding the attacking color's pieces?
+    fn is_attacked(&self, index: usize, by: Color, attackers: Bitboard, occupied: Bitboard) -> bool {
+        let [kings, queens, rooks, bishops, knights, pawns] = self.pieces;
+        KNIGHT_ATTACKS[index] & knights & attackers != 0
+            || PAWN_ATTACKS[color_index(!by)][index] & pawns & attackers != 0
+            || KING_ATTACKS[index] & kings & attackers != 0
+            || diagonal_attacks(index, occupied) & (bishops | queens) & attackers != 0
+            || (file_attacks(index, occupied) | rank_attacks(index, occupied))
+                & (rooks | queens)
+                & attackers
+                != 0
+    }
+
+    /// Is the square attacked by any piece of the given color?
+    #[inline]
+    pub(crate) fn is_attacked_by(&self, pos: Position, by: Color) -> bool {
+        self.is_attacked(square_index(pos), by, self.color(by), self.occupied())
+    }
+
+    /// Would moving a piece from `from` to `to`, capturing whatever stands
+    /// on `captured`, leave the mover's king attacked?
+    fn leaves_king_attacked(&self, color: Color, from: Position, to: Position, captured: Position) -> bool {
+        let kings = self.kings(color);
+        let king = if kings & square_bit(from) != 0 {
+            square_index(to)
+        } else if kings != 0 {
+            kings.trailing_zeros() as usize
+        } else {
+            return false;
+        };
+
+        let removed = square_bit(from) | square_bit(captured);
+        let occupied = (self.occupied() & !removed) | square_bit(to);
+        let attackers = self.color(!color) & !square_bit(captured) & !square_bit(to);
+        self.is_attacked(king, !color, attackers, occupied)
+    }
+}
+
+/// Push a move unless it would leave the mover in check.
+#[inline]
+fn push_legal(board: &Board, color: Color, from: Position, to: Position, captured: Position, result: &mut Vec<Move>) {
+    if !board.get_bitboards().leaves_king_attacked(color, from, to, captured) {
+        result.push(Move::Piece(from, to));
+    }
+}
+
+/// Push a pawn move, or all four promotions when it reaches the last row.
+#[inline]
+fn push_pawn_move(board: &Board, color: Color, from: Position, to: Position, captured: Position, result: &mut Vec<Move>) {
+    if to.get_row() != 0 && to.get_row() != 7 {
+        push_legal(board, color, from, to, captured, result);
+    } else if !board.get_bitboards().leaves_king_attacked(color, from, to, captured) {
+        let offboard = Position::new(-1, -1);
+        for promotion in [
+            Piece::Queen(color, offboard),
+            Piece::Rook(color, offboard),
+            Piece::Bishop(color, offboard),
+            Piece::Knight(color, offboard),
+        ] {
+            result.push(Move::Promotion(from, to, promotion));
+        }
+    }
+}
+
+/// Push the moves to each target, in the order of `offsets`.
+#[inline]
+fn push_offset_moves(
+    board: &Board,
+    color: Color,
+    from: Position,
+    offsets: &[(i32, i32)],
+    targets: Bitboard,
+    result: &mut Vec<Move>,
+) {
+    for (row, col) in offsets {
+        let to = Position::new(from.get_row() + row, from.get_col() + col);
+        if to.is_on_board() && targets & square_bit(to) != 0 {
+            push_legal(board, color, from, to, to, result);
+        }
+    }
+}
+
+/// Push the moves to each target, from a1 to h8.
+#[inline]
+fn push_target_moves(board: &Board, color: Color, from: Position, targets: Bitboard, result: &mut Vec<Move>) {
+    for to in squares(targets) {
+        push_legal(board, color, from, to, to, result);
+    }
+}
+
+/// Push the legal moves of a single piece, in the same order as
+/// `Piece::get_legal_moves`.
+pub(crate) fn push_piece_moves(board: &Board, piece: Piece, result: &mut Vec<Move>) {
+    let bitboards = board.get_bitboards();
+    let occupied = bitboards.occupied();
+    let color = piece.get_color();
+    let targets = !bitboards.color(color);
+    let from = piece.get_pos();
+    let index = square_index(from);
+
+    match piece {
+        Piece::Pawn(_, _) => {
+            let up = from.pawn_up(color);
+            let next_up = up.pawn_up(color);
+
+            if let Some(en_passant) = board.get_en_passant() {
+                if en_passant == up.next_left() || en_passant == up.next_right() {
+                    push_legal(board, color, from, en_passant, en_passant.pawn_back(color), result);
+                }
+            }
+
+            let is_empty = |pos: Position| pos.is_on_board() && occupied & square_bit(pos) == 0;
+            if piece.is_starting_pawn() && is_empty(up) && is_empty(next_up) {
+                push_legal(board, color, from, next_up, next_up, result);
+            }
+            if is_empty(up) {
+                push_pawn_move(board, color, from, up, up, result);
+            }
+
+            let enemies = bitboards.color(!color);
+            for to in [up.next_left(), up.next_right()] {
+                if to.is_on_board() && enemies & square_bit(to) != 0 {
+                    push_pawn_move(board, color, from, to, to, result);
+                }
+            }
+        }
+
+        Piece::King(_, _) => {
+            push_offset_moves(board, color, from, &KING_OFFSETS, targets, result);
+            if board.can_kingside_castle(color) {
+                result.push(Move::KingSideCastle);
+            }
+            if board.can_queenside_castle(color) {
+                result.push(Move::QueenSideCastle);
+            }
+        }
+
+        Piece::Queen(_, _) => {
+            push_target_moves(board, color, from, file_attacks(index, occupied) & targets, result);
+            push_target_moves(board, color, from, rank_attacks(index, occupied) & targets, result);
+            push_target_moves(board, color, from, diagonal_attacks(index, occupied) & targets, result);
+        }
+
+        Piece::Rook(_, _) => {
+            push_target_moves(board, color, from, file_attacks(index, occupied) & targets, result);
+            push_target_moves(board, color, from, rank_attacks(index, occupied) & targets, result);
+        }
+
+        Piece::Bishop(_, _) => {
+            push_target_moves(board, color, from, diagonal_attacks(index, occupied) & targets, result);
+        }
+
+        Piece::Knight(_, _) => {
+            push_offset_moves(board, color, from, &KNIGHT_OFFSETS, targets & KNIGHT_ATTACKS[index], result);
+        }
+    }
+}
+
+/// Every legal move for the given color, with the pieces taken from the
+/// eighth row down to the first, like the squares of a `Board`.
+pub(crate) fn legal_moves(board: &Board, color: Color) -> Vec<Move> {
+    let own = board.get_bitboards().color(color);
+    let mut result = Vec::new();
+    for row in (0..8).rev() {
+        for pos in squares(own & (RANK_1 << (row * 8))) {
+            if let Some(piece) = board.get_piece(pos) {
+                push_piece_moves(board, piece, &mut result);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_fen, Evaluate, A1, A8, B2, C3, D4, E4, H1, H8};
+    use alloc::vec::Vec;
+
+    /// A small xorshift generator so playouts are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_attack_tables() {
+        assert_eq!(squares(KNIGHT_ATTACKS[square_index(A1)]).count(), 2);
+        assert_eq!(squares(KNIGHT_ATTACKS[square_index(D4)]).count(), 8);
+        assert_eq!(squares(KING_ATTACKS[square_index(H8)]).count(), 3);
+        assert_eq!(squares(KING_ATTACKS[square_index(E4)]).count(), 8);
+        assert_eq!(PAWN_ATTACKS[0][square_index(B2)], square_bit(Position::new(2, 0)) | square_bit(C3));
+
+        let occupied = square_bit(D4) | square_bit(A8);
+        let attacks = diagonal_attacks(square_index(A1), occupied);
+        assert_eq!(squares(attacks).collect::<Vec<_>>(), [B2, C3, D4]);
+        let attacks = file_attacks(square_index(A1), occupied) | rank_attacks(square_index(A1), occupied);
+        assert_eq!(squares(attacks).count(), 14);
+        assert_ne!(attacks & square_bit(H1), 0);
+        assert_ne!(attacks & square_bit(A8), 0);
+    }
+
+    #[test]
+    fn test_matches_mailbox() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        let starts = [
+            Board::default(),
+            Board::horde(),
+            parse_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(),
+            parse_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(),
+            parse_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap(),
+            parse_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap(),
+        ];
+
+        for playout in 0..120 {
+            let mut board = starts[playout % starts.len()];
+            for _ in 0..60 {
+                let moves = board.get_legal_moves();
+                assert_eq!(sorted(moves.clone()), sorted(board.get_legal_moves_mailbox()), "{}", board);
+                for row in 0..8 {
+                    for col in 0..8 {
+                        let pos = Position::new(row, col);
+                        for color in [WHITE, BLACK] {
+                            assert_eq!(
+                                board.is_threatened(pos, color),
+                                board.is_threatened_mailbox(pos, color),
+                                "{} {}",
+                                pos,
+                                board
+                            );
+                        }
+                    }
+                }
+                if moves.is_empty() {
+                    break;
+                }
+                board = board.apply_eval_move(moves[rng.next() as usize % moves.len()]);
+            }
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use super::*;
+use crate::bitboard::{self, Bitboards};
+use crate::eval;
+use crate::zobrist::KEYS;
 use alloc::{string::{String, ToString}, vec::Vec,};
 use core::cmp::Ordering;
 
+/// The game phase of the starting position, where evaluation uses only
+/// the middlegame piece-square tables.
+pub const MAX_PHASE: i32 = 24;
+
 pub struct BoardBuilder { board: Board, }
 
 impl From<Board> for BoardBuilder {
@@ -24,7 +31,7 @@ impl BoardBuilder {
             pos = pos.next_left()
         }
         for _ in 0..8 {
-            *self.board.get_square(pos) = Square::from(piece.move_to(pos));
+            self.board.set_square(pos, Square::from(piece.move_to(pos)));
             pos = pos.next_right();
         }
         self
@@ -36,7 +43,7 @@ impl BoardBuilder {
             pos = pos.next_below()
         }
         for _ in 0..8 {
-            *self.board.get_square(pos) = Square::from(piece.move_to(pos));
+            self.board.set_square(pos, Square::from(piece.move_to(pos)));
             pos = pos.next_above();
         }
         self
@@ -44,7 +51,7 @@ impl BoardBuilder {
 
     pub fn piece(mut self, piece: Piece) -> Self {
         let pos = piece.get_pos();
-        *self.board.get_square(pos) = Square::from(piece);
+        self.board.set_square(pos, Square::from(piece));
         self
     }
 
@@ -102,7 +109,21 @@ impl BoardBuilder {
         self
     }
 
-    pub fn build(self) -> Board { self.board }
+    pub fn set_halfmove_clock(mut self, halfmove_clock: u32) -> Self {
+        self.board.halfmove_clock = halfmove_clock;
+        self
+    }
+
+    pub fn set_fullmove_number(mut self, fullmove_number: u32) -> Self {
+        self.board.fullmove_number = fullmove_number;
+        self
+    }
+
+    pub fn build(self) -> Board {
+        let mut board = self.board;
+        board.hash = board.compute_hash();
+        board
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -180,25 +201,25 @@ pub struct Board {
     black_castling_rights: CastlingRights,
 
     turn: Color,
+
+    /// Plies since the last capture or pawn move.
+    halfmove_clock: u32,
+    /// Starts at 1 and increases after each of black's moves.
+    fullmove_number: u32,
+
+    /// The Zobrist hash of the position, kept up to date as moves are applied.
+    hash: u64,
+    /// The Zobrist hash of the pawns alone, used to cache pawn structure.
+    pawn_hash: u64,
+
+    /// The same placement as `squares`, used to generate moves quickly.
+    bitboards: Bitboards,
 }
 
 impl Evaluate for Board {
+    /// The value of the position with the default `EvalParams`.
     #[inline]
-    fn value_for(&self, ally_color: Color) -> f64 {
-        self.squares
-            .iter()
-            .map(|square| match square.get_piece() {
-                Some(piece) => {
-                    if piece.get_color() == ally_color {
-                        piece.get_weighted_value()
-                    } else {
-                        -piece.get_weighted_value()
-                    }
-                }
-                None => 0.0,
-            })
-            .sum()
-    }
+    fn value_for(&self, ally_color: Color) -> f64 { self.value_with_params(ally_color, &EvalParams::DEFAULT) }
 
     #[inline]
     fn get_current_player_color(&self) -> Color { self.turn }
@@ -207,18 +228,52 @@ impl Evaluate for Board {
     fn apply_eval_move(&self, m: Move) -> Self { self.apply_move(m).change_turn() }
 
     #[inline]
-    fn get_legal_moves(&self) -> Vec<Move> {
-        let mut result = vec![];
-        let color = self.get_current_player_color();
-        for square in &self.squares {
-            if let Some(piece) = square.get_piece() {
-                if piece.get_color() == color {
-                    result.extend(piece.get_legal_moves(self))
-                }
-            }
+    fn get_hash(&self) -> u64 { self.hash }
+
+    #[inline]
+    fn is_current_player_in_check(&self) -> bool { self.is_in_check(self.turn) }
+
+    fn apply_null_move(&self) -> Option<Self> {
+        if !self.has_non_pawn_material(self.turn) {
+            return None;
+        }
+        Some(self.pass_turn())
+    }
+
+    fn get_noisy_moves(&self) -> Vec<Move> {
+        self.get_legal_moves()
+            .into_iter()
+            .filter(|m| matches!(m, Move::Promotion(_, _, _)) || self.get_captured_piece(*m).is_some())
+            .collect()
+    }
+
+    fn get_moving_piece(&self, m: Move) -> Option<Piece> {
+        match m {
+            Move::Piece(from, _) | Move::Promotion(from, _, _) => self.get_piece(from),
+            _ => None,
+        }
+    }
+
+    fn get_captured_piece(&self, m: Move) -> Option<Piece> {
+        match m {
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => match self.get_piece(to) {
+                Some(piece) => Some(piece),
+                // an en passant capture
+                None if Some(to) == self.en_passant => self
+                    .get_piece(from)
+                    .filter(|piece| piece.is_pawn())
+                    .map(|piece| Piece::Pawn(!piece.get_color(), to)),
+                None => None,
+            },
+            _ => None,
         }
-        result
     }
+
+    #[inline]
+    fn get_move_gain(&self, m: Move) -> f64 { search::material_gain(self, m).unwrap_or(0.0) }
+
+    #[inline]
+    fn get_legal_moves(&self) -> Vec<Move> { bitboard::legal_moves(self, self.get_current_player_color()) }
 }
 
 impl core::fmt::Display for Board {
@@ -307,7 +362,7 @@ impl Board {
     }
 
     pub fn empty() -> Self {
-        Self {
+        let mut board = Self {
             squares: [EMPTY_SQUARE; 64],
             en_passant: None,
 
@@ -315,16 +370,25 @@ impl Board {
             black_castling_rights: CastlingRights::default(),
 
             turn: WHITE,
-        }
+
+            halfmove_clock: 0,
+            fullmove_number: 1,
+
+            hash: 0,
+            pawn_hash: 0,
+            bitboards: Bitboards::default(),
+        };
+        board.hash = board.compute_hash();
+        board
     }
 
     pub fn rating_bar(&self, len: usize) -> String {
         let (best_m, _, your_best_val) = self.get_best_next_move(2);
         let (_, _, your_lowest_val) = self.get_worst_next_move(2);
-        let mut your_val = your_best_val + your_lowest_val;
+        let mut your_val = (your_best_val + your_lowest_val).get_centipawns() as f64;
         let (_, _, their_best_val) = self.apply_move(best_m).change_turn().get_best_next_move(2);
         let (_, _, their_lowest_val) = self.apply_move(best_m).change_turn().get_worst_next_move(2);
-        let mut their_val = their_best_val + their_lowest_val;
+        let mut their_val = (their_best_val + their_lowest_val).get_centipawns() as f64;
 
         if your_val < 0.0 {
             your_val = -your_val;
@@ -362,34 +426,126 @@ impl Board {
 
     pub fn get_en_passant(&self) -> Option<Position> { self.en_passant }
 
-    pub fn remove_all(&self, color: Color) -> Self {
-        let mut result = *self;
-        for square in &mut result.squares {
+    #[inline]
+    pub fn get_halfmove_clock(&self) -> u32 { self.halfmove_clock }
+
+    #[inline]
+    pub fn get_fullmove_number(&self) -> u32 { self.fullmove_number }
+
+    /// The Zobrist hash of this position: the piece placement, player to
+    /// move, castling rights and en passant file.
+    #[inline]
+    pub fn hash(&self) -> u64 { self.hash }
+
+    /// Hash the position from scratch rather than incrementally.
+    pub(crate) fn compute_hash(&self) -> u64 {
+        let mut hash = self.state_hash();
+        for square in &self.squares {
             if let Some(piece) = square.get_piece() {
-                if piece.get_color() == color {
-                    *square = EMPTY_SQUARE
-                }
+                hash ^= KEYS.piece(piece);
             }
         }
+        if self.turn == BLACK {
+            hash ^= KEYS.black_to_move();
+        }
+        hash
+    }
+
+    /// The Zobrist hash of the pawns alone, the same for every position
+    /// with the same pawns.
+    #[inline]
+    pub fn pawn_hash(&self) -> u64 { self.pawn_hash }
 
+    /// Hash the pawns from scratch rather than incrementally.
+    #[cfg(test)]
+    pub(crate) fn compute_pawn_hash(&self) -> u64 {
+        self.squares
+            .iter()
+            .filter_map(Square::get_piece)
+            .filter(Piece::is_pawn)
+            .fold(0, |hash, piece| hash ^ KEYS.piece(piece))
+    }
+
+    /// The part of the hash covering castling rights and the en passant file.
+    fn state_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in [WHITE, BLACK] {
+            let castling_rights = self.get_castling_rights(color);
+            if castling_rights.can_kingside_castle() {
+                hash ^= KEYS.castling(color, true);
+            }
+            if castling_rights.can_queenside_castle() {
+                hash ^= KEYS.castling(color, false);
+            }
+        }
+
+        if let Some(en_passant) = self.en_passant {
+            // the file only counts when an enemy pawn stands ready to capture
+            let (pawn_pos, capturing_color) = match en_passant.get_row() {
+                2 => (en_passant.next_above(), BLACK),
+                _ => (en_passant.next_below(), WHITE),
+            };
+            if [pawn_pos.next_left(), pawn_pos.next_right()]
+                .iter()
+                .any(|pos| self.get_piece(*pos) == Some(Piece::Pawn(capturing_color, *pos)))
+            {
+                hash ^= KEYS.en_passant(en_passant);
+            }
+        }
+        hash
+    }
+
+    /// The en passant square, but only if the player to move can actually
+    /// capture on it.
+    fn get_en_passant_capture(&self) -> Option<Position> {
+        let en_passant = self.en_passant?;
+        self.get_legal_moves()
+            .into_iter()
+            .any(|m| match m {
+                Move::Piece(from, to) => {
+                    to == en_passant && matches!(self.get_piece(from), Some(Piece::Pawn(_, _)))
+                }
+                _ => false,
+            })
+            .then_some(en_passant)
+    }
+
+    /// Do both boards count as the same position when looking for
+    /// repetitions? That requires the same placement, player to move,
+    /// castling rights and en passant captures.
+    pub fn is_same_position(&self, other: &Self) -> bool {
+        self.squares == other.squares
+            && self.turn == other.turn
+            && self.white_castling_rights == other.white_castling_rights
+            && self.black_castling_rights == other.black_castling_rights
+            && self.get_en_passant_capture() == other.get_en_passant_capture()
+    }
+
+    pub fn remove_all(&self, color: Color) -> Self {
+        let mut result = *self;
+        for pos in bitboard::squares(self.bitboards.color(color)) {
+            result.set_square(pos, EMPTY_SQUARE);
+        }
+
+        result.hash = result.compute_hash();
         result
     }
 
     pub fn queen_all(&self, color: Color) -> Self {
         let mut result = *self;
-        for square in &mut result.squares {
-            if let Some(piece) = square.get_piece() {
-                if !piece.is_king() && piece.get_color() == color {
-                    *square = Square::from(Piece::Queen(color, piece.get_pos()))
-                }
-            }
+        for pos in bitboard::squares(self.bitboards.color(color) & !self.bitboards.kings(color)) {
+            result.set_square(pos, Square::from(Piece::Queen(color, pos)));
         }
+        result.hash = result.compute_hash();
         result
     }
 
     #[inline]
     pub fn set_turn(&self, color: Color) -> Self {
         let mut result = *self;
+        if result.turn != color {
+            result.hash ^= KEYS.black_to_move();
+        }
         result.turn = color;
         result
     }
@@ -411,13 +567,35 @@ impl Board {
             .sum()
     }
 
+    /// Replace the contents of a square, keeping the hash and bitboards
+    /// up to date.
+    #[inline]
+    fn set_square(&mut self, pos: Position, square: Square) {
+        let old_square = &mut self.squares[((7 - pos.get_row()) * 8 + pos.get_col()) as usize];
+        if let Some(piece) = old_square.get_piece() {
+            self.hash ^= KEYS.piece(piece);
+            if piece.is_pawn() {
+                self.pawn_hash ^= KEYS.piece(piece);
+            }
+            self.bitboards.toggle(piece);
+        }
+        if let Some(piece) = square.get_piece() {
+            self.hash ^= KEYS.piece(piece);
+            if piece.is_pawn() {
+                self.pawn_hash ^= KEYS.piece(piece);
+            }
+            self.bitboards.toggle(piece);
+        }
+        *old_square = square;
+    }
+
     #[inline]
-    fn get_square(&mut self, pos: Position) -> &mut Square { &mut self.squares[((7 - pos.get_row()) * 8 + pos.get_col()) as usize] }
+    pub(crate) fn get_bitboards(&self) -> &Bitboards { &self.bitboards }
 
     #[inline]
     fn add_piece(&mut self, piece: Piece) {
         let pos = piece.get_pos();
-        *self.get_square(pos) = Square::from(piece);
+        self.set_square(pos, Square::from(piece));
     }
 
     #[inline]
@@ -447,24 +625,23 @@ impl Board {
     }
 
     #[inline]
-    pub fn has_piece(&self, pos: Position) -> bool { self.get_piece(pos) != None }
+    pub fn has_piece(&self, pos: Position) -> bool { self.get_piece(pos).is_some() }
 
     #[inline]
-    pub fn has_no_piece(&self, pos: Position) -> bool { self.get_piece(pos) == None }
+    pub fn has_no_piece(&self, pos: Position) -> bool { self.get_piece(pos).is_none() }
 
     pub fn get_king_pos(&self, color: Color) -> Option<Position> {
-        let mut king_pos = None;
-        for square in &self.squares {
-            if let Some(Piece::King(c, pos)) = square.get_piece() {
-                if c == color {
-                    king_pos = Some(pos);
-                }
-            }
-        }
-        king_pos
+        bitboard::squares(self.bitboards.kings(color)).next()
     }
 
+    /// Is the square attacked by a piece of the other color? A square
+    /// holding one of the other color's pieces is never threatened.
     pub fn is_threatened(&self, pos: Position, ally_color: Color) -> bool {
+        !self.has_enemy_piece(pos, ally_color) && self.bitboards.is_attacked_by(pos, !ally_color)
+    }
+
+    /// `is_threatened`, checking each piece's moves one by one.
+    pub(crate) fn is_threatened_mailbox(&self, pos: Position, ally_color: Color) -> bool {
         for (i, square) in self.squares.iter().enumerate() {
             let row = 7 - i / 8;
             let col = i % 8;
@@ -498,17 +675,42 @@ impl Board {
         }
     }
 
+    fn is_in_check_mailbox(&self, color: Color) -> bool {
+        if let Some(king_pos) = self.get_king_pos(color) {
+            self.is_threatened_mailbox(king_pos, color)
+        } else {
+            false
+        }
+    }
+
+    /// Generate the legal moves one piece at a time, without bitboards.
+    /// This is the original move generator, kept to check and benchmark
+    /// `get_legal_moves` against.
+    #[doc(hidden)]
+    pub fn get_legal_moves_mailbox(&self) -> Vec<Move> {
+        let mut result = vec![];
+        let color = self.get_current_player_color();
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                if piece.get_color() == color {
+                    result.extend(piece.get_legal_moves(self))
+                }
+            }
+        }
+        result
+    }
+
     fn move_piece(&self, from: Position, to: Position, promotion: Option<Piece>) -> Self {
         let mut result = *self;
         result.en_passant = None;
 
         if from.is_off_board() || to.is_off_board() {
+            result.hash ^= self.state_hash() ^ result.state_hash();
             return result;
         }
 
-        let from_square = result.get_square(from);
-        if let Some(mut piece) = from_square.get_piece() {
-            *from_square = EMPTY_SQUARE;
+        if let Some(mut piece) = result.get_piece(from) {
+            result.set_square(from, EMPTY_SQUARE);
 
             if piece.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
                 piece = match promotion {
@@ -546,8 +748,18 @@ impl Board {
             } else if piece.is_kingside_rook() {
                 castling_rights.disable_kingside();
             }
+
+            // capturing a rook in its corner takes away that castle too
+            match to {
+                A1 => result.white_castling_rights.disable_queenside(),
+                H1 => result.white_castling_rights.disable_kingside(),
+                A8 => result.black_castling_rights.disable_queenside(),
+                H8 => result.black_castling_rights.disable_kingside(),
+                _ => {}
+            }
         }
 
+        result.hash ^= self.state_hash() ^ result.state_hash();
         result
     }
 
@@ -590,6 +802,7 @@ impl Board {
                     && self.white_castling_rights.can_queenside_castle()
                     && !self.is_in_check(color)
                     && !self.is_threatened(Position::queen_pos(color), color)
+                    && !self.is_threatened(Position::queen_pos(color).next_left(), color)
             }
             BLACK => {
                 self.has_no_piece(Position::new(7, 1))
@@ -600,6 +813,7 @@ impl Board {
                     && self.black_castling_rights.can_queenside_castle()
                     && !self.is_in_check(color)
                     && !self.is_threatened(Position::queen_pos(color), color)
+                    && !self.is_threatened(Position::queen_pos(color).next_left(), color)
             }
         }
     }
@@ -612,6 +826,33 @@ impl Board {
     }
 
     pub(crate) fn is_legal_move(&self, m: Move, player_color: Color) -> bool {
+        match m {
+            Move::KingSideCastle => self.can_kingside_castle(player_color),
+            Move::QueenSideCastle => self.can_queenside_castle(player_color),
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => match self.get_piece(from) {
+                Some(piece) if piece.get_color() == player_color => {
+                    let mut moves = Vec::new();
+                    bitboard::push_piece_moves(self, piece, &mut moves);
+                    moves.into_iter().any(|legal| match (legal, m) {
+                        (Move::Piece(_, legal_to), Move::Piece(_, _)) => legal_to == to,
+                        // a pawn moving onto the last row without a choice of piece becomes a queen
+                        (Move::Promotion(_, legal_to, promotion), Move::Piece(_, _)) => {
+                            legal_to == to && promotion.is_queen()
+                        }
+                        (Move::Promotion(_, legal_to, legal_promotion), Move::Promotion(_, _, promotion)) => {
+                            legal_to == to && legal_promotion.kind_index() == promotion.kind_index()
+                        }
+                        _ => false,
+                    })
+                }
+                _ => false,
+            },
+            Move::Resign => true,
+        }
+    }
+
+    /// `is_legal_move` for the moves of `Piece::get_legal_moves`.
+    pub(crate) fn is_legal_move_mailbox(&self, m: Move, player_color: Color) -> bool {
         match m {
             Move::KingSideCastle => self.can_kingside_castle(player_color),
             Move::QueenSideCastle => self.can_queenside_castle(player_color),
@@ -620,18 +861,18 @@ impl Board {
                     let piece = Piece::Pawn(c, pos);
                     ((if let Some(en_passant) = self.en_passant {
                         (en_passant == from.pawn_up(player_color).next_left()
-                            || en_passant == from.pawn_up(player_color).next_right()
-                                && en_passant == to)
+                            || en_passant == from.pawn_up(player_color).next_right())
+                            && en_passant == to
                             && c == player_color
                     } else {
                         false
                     }) || piece.is_legal_move(to, self) && piece.get_color() == player_color)
-                        && !self.apply_move(m).is_in_check(player_color)
+                        && !self.apply_move(m).is_in_check_mailbox(player_color)
                 }
                 Some(piece) => {
                     piece.is_legal_move(to, self)
                         && piece.get_color() == player_color
-                        && !self.apply_move(m).is_in_check(player_color)
+                        && !self.apply_move(m).is_in_check_mailbox(player_color)
                 }
                 _ => false,
             },
@@ -645,7 +886,7 @@ impl Board {
                             // regular piece checks
                             && piece.is_legal_move(to, self)
                             && piece.get_color() == player_color
-                            && !self.apply_move(m).is_in_check(player_color)
+                            && !self.apply_move(m).is_in_check_mailbox(player_color)
                     }
                     _ => false,
                 }
@@ -667,29 +908,18 @@ impl Board {
 
         pieces.sort();
 
-        if pieces.len() == 0 {
-            false
-        } else if pieces.len() == 1 && pieces[0].is_king() {
-            false
-        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_knight() {
-            false
-        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_bishop() {
-            false
-        } else if pieces.len() == 3
-            && pieces[0].is_king()
-            && pieces[1].is_knight()
-            && pieces[2].is_knight()
-        {
-            false
-        } else if pieces.len() == 3
-            && pieces[0].is_king()
-            && pieces[1].is_bishop()
-            && pieces[2].is_bishop()
-        {
-            false
-        } else {
-            true
-        }
+        !(pieces.is_empty()
+            || pieces.len() == 1 && pieces[0].is_king()
+            || pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_knight()
+            || pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_bishop()
+            || pieces.len() == 3
+                && pieces[0].is_king()
+                && pieces[1].is_knight()
+                && pieces[2].is_knight()
+            || pieces.len() == 3
+                && pieces[0].is_king()
+                && pieces[1].is_bishop()
+                && pieces[2].is_bishop())
     }
 
     /// Does the respective player have insufficient material?
@@ -698,6 +928,116 @@ impl Board {
         !self.has_sufficient_material(color)
     }
 
+    /// Does the respective player have any pieces besides their king and
+    /// pawns? Without them, zugzwang is likely.
+    #[inline]
+    pub fn has_non_pawn_material(&self, color: Color) -> bool { self.bitboards.non_pawn_material(color) != 0 }
+
+    /// The material, piece-square, pawn structure, mobility, king safety
+    /// and tempo value of the position for the given color, blended from
+    /// its middlegame and endgame values by the game phase.
+    pub fn value_with_params(&self, ally_color: Color, params: &EvalParams) -> f64 {
+        let weights = &params.weights;
+        let mut white = eval::cached_pawn_structure(&self.bitboards, weights, self.pawn_hash);
+        for piece in self.squares.iter().filter_map(Square::get_piece) {
+            let material = params.get_material(&piece);
+            let value = params.get_square_values(&piece) + EvalTerm::new(material, material);
+            match piece.get_color() {
+                WHITE => white = white + value,
+                BLACK => white = white - value,
+            }
+        }
+        for (color, sign) in [(WHITE, 1.0), (BLACK, -1.0)] {
+            let (mobility, king_safety) = eval::mobility_and_king_safety(self, weights, color);
+            white = white + (mobility + king_safety.total()) * sign;
+        }
+        white = match self.turn {
+            WHITE => white + weights.tempo,
+            BLACK => white - weights.tempo,
+        };
+
+        let value = white.blend(self.game_phase(params));
+        if ally_color == WHITE { value } else { -value }
+    }
+
+    /// Value the board with `evaluator` instead of `value_for` when it is
+    /// searched.
+    #[inline]
+    pub fn with_evaluator<V: Evaluator>(self, evaluator: &V) -> EvaluatedBoard<'_, V> {
+        EvaluatedBoard::new(self, evaluator)
+    }
+
+    /// The evaluation of the position for the given color, broken down into
+    /// each of its terms for each side.
+    pub fn evaluate_trace(&self, color: Color) -> EvalTrace { self.evaluate_trace_with_params(color, &EvalParams::DEFAULT) }
+
+    /// Like `evaluate_trace`, but with the given parameters, matching
+    /// `value_with_params`.
+    pub fn evaluate_trace_with_params(&self, color: Color, params: &EvalParams) -> EvalTrace {
+        let weights = &params.weights;
+        let mut trace = EvalTrace {
+            material: TraceTerm::default(),
+            piece_squares: TraceTerm::default(),
+            pawns: TraceTerm::default(),
+            king_safety: TraceTerm::default(),
+            mobility: TraceTerm::default(),
+            tempo: TraceTerm::default(),
+            phase: self.game_phase(params),
+            color,
+        };
+        for piece in self.squares.iter().filter_map(Square::get_piece) {
+            let material = params.get_material(&piece);
+            let (material_side, square_side) = match piece.get_color() {
+                WHITE => (&mut trace.material.white, &mut trace.piece_squares.white),
+                BLACK => (&mut trace.material.black, &mut trace.piece_squares.black),
+            };
+            *material_side = *material_side + EvalTerm::new(material, material);
+            *square_side = *square_side + params.get_square_values(&piece);
+        }
+        for color in [WHITE, BLACK] {
+            let pawns = eval::pawn_structure(&self.bitboards, weights, color).total();
+            let (mobility, king_safety) = eval::mobility_and_king_safety(self, weights, color);
+            let tempo = if self.turn == color { weights.tempo } else { EvalTerm::default() };
+            for (term, value) in [
+                (&mut trace.pawns, pawns),
+                (&mut trace.mobility, mobility),
+                (&mut trace.king_safety, king_safety.total()),
+                (&mut trace.tempo, tempo),
+            ] {
+                match color {
+                    WHITE => term.white = value,
+                    BLACK => term.black = value,
+                }
+            }
+        }
+        trace
+    }
+
+    /// The pawn structure of the position term by term, from white's point
+    /// of view.
+    pub fn get_pawn_structure(&self) -> PawnStructure {
+        let weights = &EvalWeights::DEFAULT;
+        eval::pawn_structure(&self.bitboards, weights, WHITE) - eval::pawn_structure(&self.bitboards, weights, BLACK)
+    }
+
+    /// How much material is left to make it a middlegame, from 0 with only
+    /// kings and pawns to `MAX_PHASE` with every piece of the starting
+    /// position, weighted by `EvalParams::get_phase_weight` with the
+    /// default parameters.
+    pub fn get_game_phase(&self) -> i32 { self.game_phase(&EvalParams::DEFAULT) }
+
+    /// The game phase with the given parameters' phase weights.
+    fn game_phase(&self, params: &EvalParams) -> i32 {
+        let phase: i32 = self
+            .squares
+            .iter()
+            .filter_map(Square::get_piece)
+            .map(|piece| params.get_phase_weight(&piece))
+            .sum();
+        // promotions can add more than there was to begin with
+        phase.min(MAX_PHASE)
+    }
+
     /// Is the current player in stalemate?
     pub fn is_stalemate(&self) -> bool {
         (self.get_legal_moves().is_empty() && !self.is_in_check(self.get_current_player_color()))
@@ -710,14 +1050,40 @@ impl Board {
         self.is_in_check(self.get_current_player_color()) && self.get_legal_moves().is_empty()
     }
 
+    /// Hand the move to the other player without moving a piece, which
+    /// also gives up any en passant capture.
+    pub(crate) fn pass_turn(&self) -> Self {
+        let mut result = *self;
+        result.en_passant = None;
+        result.hash ^= self.state_hash() ^ result.state_hash();
+        result.change_turn()
+    }
+
     /// Change the current turn to the next player.
     #[inline]
     pub fn change_turn(mut self) -> Self {
         self.turn = !self.turn;
+        self.hash ^= KEYS.black_to_move();
         self
     }
 
     fn apply_move(&self, m: Move) -> Self {
+        let mut result = self.apply_move_placement(m);
+
+        let resets_clock = match m {
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+                self.has_piece(to) || matches!(self.get_piece(from), Some(Piece::Pawn(_, _)))
+            }
+            _ => false,
+        };
+        result.halfmove_clock = if resets_clock { 0 } else { self.halfmove_clock + 1 };
+        if self.turn == BLACK {
+            result.fullmove_number = self.fullmove_number + 1;
+        }
+        result
+    }
+
+    fn apply_move_placement(&self, m: Move) -> Self {
         match m {
             Move::KingSideCastle => {
                 if let Some(king_pos) = self.get_king_pos(self.turn) {
@@ -754,9 +1120,7 @@ impl Board {
                         || en_passant == from.pawn_up(player_color).next_right())
                         && en_passant == to
                     {
-                        result.squares[((7 - en_passant.pawn_back(player_color).get_row()) * 8
-                            + en_passant.get_col())
-                            as usize] = EMPTY_SQUARE;
+                        result.set_square(en_passant.pawn_back(player_color), EMPTY_SQUARE);
                     }
                 }
 
@@ -767,7 +1131,30 @@ impl Board {
         }
     }
 
-    /// Play a move and confirm it is legal.
+    /// Count the positions reached after exactly `depth` plies, for
+    /// checking the move generator against published results.
+    pub fn perft(&self, depth: u32) -> u64 {
+        match depth {
+            0 => 1,
+            1 => self.get_legal_moves().len() as u64,
+            _ => self
+                .get_legal_moves()
+                .into_iter()
+                .map(|m| self.apply_eval_move(m).perft(depth - 1))
+                .sum(),
+        }
+    }
+
+    /// Split the `perft` count up by the first move played.
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        self.get_legal_moves()
+            .into_iter()
+            .map(|m| (m, self.apply_eval_move(m).perft(depth.saturating_sub(1))))
+            .collect()
+    }
+
+    /// Play a move and confirm it is legal. A `Move::Piece` taking a pawn
+    /// onto the last row promotes it to a queen.
     pub fn play_move(&self, m: Move) -> GameResult {
         let current_color = self.get_turn_color();
 
@@ -786,4 +1173,181 @@ impl Board {
             GameResult::IllegalMove(m)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The published results for the well-known perft positions, from
+    /// https://www.chessprogramming.org/Perft_Results.
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+    const PROMOTIONS: &str = "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1";
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let board = parse_fen(fen).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(board.perft(depth as u32 + 1), *nodes, "{} at depth {}", fen, depth + 1);
+        }
+    }
+
+    #[test]
+    fn test_perft_start() { assert_perft(START, &[20, 400, 8902, 197281]); }
+
+    #[test]
+    fn test_perft_kiwipete() { assert_perft(KIWIPETE, &[48, 2039, 97862, 4085603]); }
+
+    #[test]
+    fn test_perft_position_3() { assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]); }
+
+    #[test]
+    fn test_perft_position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467, 422333]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    fn test_perft_position_5() { assert_perft(POSITION_5, &[44, 1486, 62379, 2103487]); }
+
+    #[test]
+    fn test_perft_position_6() { assert_perft(POSITION_6, &[46, 2079, 89890, 3894594]); }
+
+    #[test]
+    fn test_perft_promotions() { assert_perft(PROMOTIONS, &[24, 496, 9483, 182838]); }
+
+    #[test]
+    fn test_promotion_moves() {
+        let board = parse_fen("1r5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let moves = board.get_legal_moves();
+        let promotions = moves
+            .iter()
+            .filter_map(|m| match m {
+                Move::Promotion(from, to, promotion) => Some((*from, *to, promotion.get_name())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            promotions,
+            [
+                (A7, A8, "queen"),
+                (A7, A8, "rook"),
+                (A7, A8, "bishop"),
+                (A7, A8, "knight"),
+                (A7, B8, "queen"),
+                (A7, B8, "rook"),
+                (A7, B8, "bishop"),
+                (A7, B8, "knight"),
+            ]
+        );
+        assert!(!moves.contains(&Move::Piece(A7, A8)));
+
+        // a bare move onto the last row promotes to a queen
+        match board.play_move(Move::Piece(A7, B8)) {
+            GameResult::Continuing(next) => assert_eq!(next.get_piece(B8), Some(Piece::Queen(WHITE, B8))),
+            other => panic!("unexpected {:?}", other),
+        }
+        match board.play_move(Move::Promotion(A7, A8, Piece::Knight(WHITE, A8))) {
+            GameResult::Continuing(next) => assert_eq!(next.get_piece(A8), Some(Piece::Knight(WHITE, A8))),
+            other => panic!("unexpected {:?}", other),
+        }
+        for promotion in [Piece::King(WHITE, A8), Piece::Pawn(WHITE, A8)] {
+            let m = Move::Promotion(A7, A8, promotion);
+            assert_eq!(board.play_move(m), GameResult::IllegalMove(m));
+        }
+        let m = Move::Promotion(A1, A2, Piece::Queen(WHITE, A2));
+        assert_eq!(board.play_move(m), GameResult::IllegalMove(m));
+    }
+
+    #[test]
+    fn test_divide() {
+        let board = parse_fen(KIWIPETE).unwrap();
+        let divide = board.divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+        assert!(divide.contains(&(Move::KingSideCastle, 43)));
+        assert!(divide.contains(&(Move::QueenSideCastle, 43)));
+        assert_eq!(board.divide(0).len(), 48);
+    }
+
+    #[test]
+    fn test_rook_capture_castling_rights() {
+        let board = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let board = board.apply_eval_move(Move::Piece(A1, A8));
+        assert!(!board.get_castling_rights(WHITE).can_queenside_castle());
+        assert!(!board.get_castling_rights(BLACK).can_queenside_castle());
+        assert!(board.get_castling_rights(BLACK).can_kingside_castle());
+        assert_eq!(board.hash(), board.compute_hash());
+    }
+
+    #[test]
+    fn test_null_move() {
+        let board = parse_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let passed = board.apply_null_move().unwrap();
+        assert_eq!(passed.get_turn_color(), WHITE);
+        assert_eq!(passed.get_en_passant(), None);
+        assert_eq!(passed.hash(), passed.compute_hash());
+
+        // with only pawns left, passing could be better than any move
+        let board = parse_fen("4k3/4p3/8/8/8/8/4P3/4K2R b K - 0 1").unwrap();
+        assert!(!board.has_non_pawn_material(BLACK));
+        assert!(board.has_non_pawn_material(WHITE));
+        assert!(board.apply_null_move().is_none());
+    }
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(parse_fen(START).unwrap().get_game_phase(), MAX_PHASE);
+        assert_eq!(parse_fen(POSITION_3).unwrap().get_game_phase(), 4);
+        assert_eq!(parse_fen("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap().get_game_phase(), 8);
+        assert_eq!(parse_fen("7k/8/p7/P7/8/8/8/7K w - - 0 1").unwrap().get_game_phase(), 0);
+        assert_eq!(parse_fen("QQQQQQQk/8/8/8/8/8/8/QQQQQQQK w - - 0 1").unwrap().get_game_phase(), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_evaluate_trace() {
+        for fen in [START, KIWIPETE, POSITION_3, POSITION_4, POSITION_4_MIRRORED, POSITION_5] {
+            let board = parse_fen(fen).unwrap();
+            for color in [WHITE, BLACK] {
+                let trace = board.evaluate_trace(color);
+                assert!((trace.value() - board.value_for(color)).abs() < 1e-6, "{}", fen);
+                assert_eq!(trace.phase, board.get_game_phase());
+            }
+        }
+
+        let trace = parse_fen(START).unwrap().evaluate_trace(WHITE);
+        assert_eq!(trace.material.white, EvalTerm::new(390.0, 390.0));
+        assert_eq!(trace.material.get_net(), EvalTerm::default());
+        assert_eq!(trace.piece_squares.get_net(), EvalTerm::default());
+        assert_eq!(trace.tempo.white, EvalWeights::DEFAULT.tempo);
+        assert_eq!(trace.tempo.black, EvalTerm::default());
+        assert_eq!(trace.total(), EvalWeights::DEFAULT.tempo);
+    }
+
+    #[test]
+    fn test_tapered_king_placement() {
+        let value = |fen: &str| parse_fen(fen).unwrap().value_for(WHITE);
+
+        // with the pieces on, the king is safest castled
+        let castled = value("rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w - - 0 1");
+        let central = value("rnbq1rk1/pppppppp/8/8/3K4/8/PPPPPPPP/RNBQ1R2 w - - 0 1");
+        assert!(castled > central);
+
+        // with only pawns left, it belongs in the center
+        let cornered = value("6k1/8/p7/P7/8/8/8/6K1 w - - 0 1");
+        let central = value("6k1/8/p7/P7/3K4/8/8/8 w - - 0 1");
+        assert!(central > cornered);
+
+        // so the king walks towards the center
+        let board = parse_fen("7k/8/p7/P7/8/8/8/7K w - - 0 1").unwrap();
+        assert_eq!(board.get_best_next_move(3).0, Move::Piece(H1, G2));
+        let board = parse_fen("7k/8/p7/P7/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(board.get_best_next_move(3).0, Move::Piece(H8, G7));
+    }
+}
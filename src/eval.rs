@@ -0,0 +1,821 @@
+use super::{search, Board, Color, EvalParams, Evaluate, Move, Piece, BLACK, MAX_PHASE, WHITE};
+use alloc::vec::Vec;
+use crate::bitboard::{piece_attacks, square_bit, square_index, squares, Bitboard, Bitboards};
+use core::{
+    ops::{Add, Mul, Neg, Sub},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// A term of the evaluation, in tenths of a pawn, with separate values for
+/// the middlegame and the endgame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalTerm {
+    pub middlegame: f64,
+    pub endgame: f64,
+}
+
+impl EvalTerm {
+    #[inline]
+    pub const fn new(middlegame: f64, endgame: f64) -> Self { Self { middlegame, endgame } }
+
+    /// Blend the two values by the game phase, from 0 in a pawn endgame to
+    /// `MAX_PHASE` in the starting position.
+    #[inline]
+    pub fn blend(&self, phase: i32) -> f64 {
+        let phase = phase.clamp(0, MAX_PHASE) as f64;
+        (self.middlegame * phase + self.endgame * (MAX_PHASE as f64 - phase)) / MAX_PHASE as f64
+    }
+}
+
+impl Add for EvalTerm {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self { Self::new(self.middlegame + other.middlegame, self.endgame + other.endgame) }
+}
+
+impl Sub for EvalTerm {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self { self + -other }
+}
+
+impl Neg for EvalTerm {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self { Self::new(-self.middlegame, -self.endgame) }
+}
+
+impl Mul<f64> for EvalTerm {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, factor: f64) -> Self { Self::new(self.middlegame * factor, self.endgame * factor) }
+}
+
+/// The pawn structure terms of a position, from white's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnStructure {
+    /// Every pawn behind another of its color on the same file.
+    pub doubled: EvalTerm,
+    /// Pawns with no pawns of their color on the neighbouring files.
+    pub isolated: EvalTerm,
+    /// Pawns that the pawns beside them have left behind, and can't
+    /// safely advance to catch up.
+    pub backward: EvalTerm,
+    /// Pawns with no enemy pawns in front of them or on the neighbouring
+    /// files, so nothing but pieces can stop them promoting.
+    pub passed: EvalTerm,
+}
+
+impl PawnStructure {
+    /// The sum of every term.
+    #[inline]
+    pub fn total(&self) -> EvalTerm { self.doubled + self.isolated + self.backward + self.passed }
+}
+
+impl Sub for PawnStructure {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            doubled: self.doubled - other.doubled,
+            isolated: self.isolated - other.isolated,
+            backward: self.backward - other.backward,
+            passed: self.passed - other.passed,
+        }
+    }
+}
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+const FILE_H: Bitboard = FILE_A << 7;
+
+#[inline]
+fn file(index: usize) -> Bitboard { FILE_A << (index % 8) }
+
+/// The files either side of the square's file.
+#[inline]
+fn adjacent_files(index: usize) -> Bitboard { ((file(index) << 1) & !FILE_A) | ((file(index) >> 1) & !FILE_H) }
+
+/// The squares on ranks in front of the square, from the color's side.
+#[inline]
+fn ranks_in_front(color: Color, index: usize) -> Bitboard {
+    let rank = index / 8;
+    match color {
+        WHITE if rank == 7 => 0,
+        WHITE => !0 << ((rank + 1) * 8),
+        BLACK => (1 << (rank * 8)) - 1,
+    }
+}
+
+/// The squares the color's pawns attack.
+#[inline]
+fn pawn_attacks(color: Color, pawns: Bitboard) -> Bitboard {
+    match color {
+        WHITE => ((pawns << 7) & !FILE_H) | ((pawns << 9) & !FILE_A),
+        BLACK => ((pawns >> 9) & !FILE_H) | ((pawns >> 7) & !FILE_A),
+    }
+}
+
+/// The rank of the square, counted from the color's side of the board.
+#[inline]
+fn relative_rank(color: Color, index: usize) -> usize {
+    match color {
+        WHITE => index / 8,
+        BLACK => 7 - index / 8,
+    }
+}
+
+/// The terms that depend on the color's pawns alone, leaving out the
+/// passed pawn bonus, which depends on the pieces in their way, along with
+/// the color's passed pawns.
+fn pawn_terms(bitboards: &Bitboards, weights: &EvalWeights, color: Color) -> (PawnStructure, Bitboard) {
+    let mut structure = PawnStructure::default();
+    let mut passed = 0;
+    let pawns = bitboards.pawns(color);
+    let enemy_pawns = bitboards.pawns(!color);
+    let enemy_attacks = pawn_attacks(!color, enemy_pawns);
+
+    for index in squares(pawns).map(square_index) {
+        let in_front = ranks_in_front(color, index);
+        let neighbours = pawns & adjacent_files(index);
+
+        if pawns & file(index) & in_front != 0 {
+            structure.doubled = structure.doubled + weights.doubled_pawn;
+        }
+
+        let is_passed = (enemy_pawns & (file(index) | adjacent_files(index)) & in_front) == 0
+            && (pawns & file(index) & in_front) == 0;
+        if is_passed {
+            passed |= 1 << index;
+        }
+
+        if neighbours == 0 {
+            structure.isolated = structure.isolated + weights.isolated_pawn;
+        } else if !is_passed && neighbours & !in_front == 0 {
+            let stop = match color {
+                WHITE => index + 8,
+                BLACK => index - 8,
+            };
+            if enemy_attacks & (1 << stop) != 0 {
+                structure.backward = structure.backward + weights.backward_pawn;
+            }
+        }
+    }
+    (structure, passed)
+}
+
+/// The bonus for the color's passed pawns, scaled down for each one with a
+/// piece in front of it.
+fn passed_pawn_term(bitboards: &Bitboards, weights: &EvalWeights, passed: Bitboard, color: Color) -> EvalTerm {
+    let occupied = bitboards.occupied();
+    let mut term = EvalTerm::default();
+    for index in squares(passed & bitboards.pawns(color)).map(square_index) {
+        let mut bonus = weights.passed_pawn[relative_rank(color, index)];
+        if occupied & file(index) & ranks_in_front(color, index) != 0 {
+            bonus = bonus * weights.blocked_passed_pawn;
+        }
+        term = term + bonus;
+    }
+    term
+}
+
+/// Evaluate the color's pawn structure term by term, without the cache.
+pub(crate) fn pawn_structure(bitboards: &Bitboards, weights: &EvalWeights, color: Color) -> PawnStructure {
+    let (mut structure, passed) = pawn_terms(bitboards, weights, color);
+    structure.passed = passed_pawn_term(bitboards, weights, passed, color);
+    structure
+}
+
+/// The weights of the pawn structure, mobility and king safety terms, in
+/// tenths of a pawn. Arrays by kind of piece are indexed from king to
+/// pawn, like `Piece::kind_index`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvalWeights {
+    /// For each pawn behind another of its color on the same file.
+    pub doubled_pawn: EvalTerm,
+    /// For each pawn with no pawns of its color on the neighbouring files.
+    pub isolated_pawn: EvalTerm,
+    /// For each pawn left behind by the pawns beside it.
+    pub backward_pawn: EvalTerm,
+    /// For a passed pawn on each rank, counted from its own side of the
+    /// board. It grows quickly as the pawn nears promotion.
+    pub passed_pawn: [EvalTerm; 8],
+    /// How much of its bonus a passed pawn keeps when a piece stands in its
+    /// way.
+    pub blocked_passed_pawn: f64,
+    /// For each square a piece attacks that isn't held by its own side or
+    /// guarded by an enemy pawn.
+    pub mobility: [EvalTerm; 6],
+    /// For each pawn on the two ranks in front of its king, on the king's
+    /// file or either side of it.
+    pub pawn_shield: EvalTerm,
+    /// For each file beside or under the king without pawns of its color.
+    pub semi_open_file: EvalTerm,
+    /// For each file beside or under the king without any pawns at all, on
+    /// top of `semi_open_file`.
+    pub open_file: EvalTerm,
+    /// For each square around the enemy king a piece attacks.
+    pub king_attack: [EvalTerm; 6],
+    /// How much of the king attack weight counts, by the number of pieces
+    /// taking part. A lone attacker is little danger.
+    pub king_attackers: [f64; 8],
+    /// For the side to move, which can often improve its position before
+    /// the other side gets to.
+    pub tempo: EvalTerm,
+}
+
+impl EvalWeights {
+    pub const DEFAULT: Self = Self {
+        doubled_pawn: EvalTerm::new(-1.0, -2.0),
+        isolated_pawn: EvalTerm::new(-1.0, -1.5),
+        backward_pawn: EvalTerm::new(-0.8, -1.0),
+        passed_pawn: [
+            EvalTerm::new(0.0, 0.0),
+            EvalTerm::new(0.5, 1.0),
+            EvalTerm::new(1.0, 1.5),
+            EvalTerm::new(1.5, 2.5),
+            EvalTerm::new(2.5, 4.0),
+            EvalTerm::new(4.0, 6.5),
+            EvalTerm::new(6.0, 10.0),
+            EvalTerm::new(0.0, 0.0),
+        ],
+        blocked_passed_pawn: 0.5,
+        mobility: [
+            EvalTerm::new(0.0, 0.0),
+            EvalTerm::new(0.1, 0.2),
+            EvalTerm::new(0.2, 0.4),
+            EvalTerm::new(0.5, 0.5),
+            EvalTerm::new(0.4, 0.4),
+            EvalTerm::new(0.0, 0.0),
+        ],
+        pawn_shield: EvalTerm::new(1.0, 0.0),
+        semi_open_file: EvalTerm::new(-1.0, 0.0),
+        open_file: EvalTerm::new(-1.0, 0.0),
+        king_attack: [
+            EvalTerm::new(0.0, 0.0),
+            EvalTerm::new(2.5, 0.0),
+            EvalTerm::new(1.5, 0.0),
+            EvalTerm::new(1.0, 0.0),
+            EvalTerm::new(1.0, 0.0),
+            EvalTerm::new(0.0, 0.0),
+        ],
+        king_attackers: [0.0, 0.0, 0.5, 0.75, 0.88, 0.94, 0.97, 0.99],
+        tempo: EvalTerm::new(1.0, 0.5),
+    };
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self { Self::DEFAULT }
+}
+
+/// The king safety terms of a position, from white's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KingSafety {
+    /// Pawns standing in front of their king.
+    pub pawn_shield: EvalTerm,
+    /// Files without pawns beside or under the king.
+    pub open_files: EvalTerm,
+    /// Pieces attacking the squares around the enemy king.
+    pub king_attacks: EvalTerm,
+}
+
+impl KingSafety {
+    /// The sum of every term.
+    #[inline]
+    pub fn total(&self) -> EvalTerm { self.pawn_shield + self.open_files + self.king_attacks }
+}
+
+impl Sub for KingSafety {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            pawn_shield: self.pawn_shield - other.pawn_shield,
+            open_files: self.open_files - other.open_files,
+            king_attacks: self.king_attacks - other.king_attacks,
+        }
+    }
+}
+
+/// One term of an `EvalTrace`, for each side from its own point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraceTerm {
+    pub white: EvalTerm,
+    pub black: EvalTerm,
+}
+
+impl TraceTerm {
+    /// What the term is worth to white.
+    #[inline]
+    pub fn get_net(&self) -> EvalTerm { self.white - self.black }
+}
+
+/// The evaluation of a position broken down term by term, as returned by
+/// `Board::evaluate_trace`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvalTrace {
+    /// The pieces' base values.
+    pub material: TraceTerm,
+    /// The piece-square tables.
+    pub piece_squares: TraceTerm,
+    pub pawns: TraceTerm,
+    pub king_safety: TraceTerm,
+    pub mobility: TraceTerm,
+    /// The bonus for the side to move.
+    pub tempo: TraceTerm,
+    /// The game phase the terms are blended by.
+    pub phase: i32,
+    /// The side the evaluation is for.
+    pub color: Color,
+}
+
+impl EvalTrace {
+    /// Every term with its name, in the order they should be listed.
+    pub fn terms(&self) -> [(&'static str, TraceTerm); 6] {
+        [
+            ("Material", self.material),
+            ("Piece-square", self.piece_squares),
+            ("Pawns", self.pawns),
+            ("King safety", self.king_safety),
+            ("Mobility", self.mobility),
+            ("Tempo", self.tempo),
+        ]
+    }
+
+    /// The sum of every term, from white's point of view.
+    pub fn total(&self) -> EvalTerm {
+        self.terms()
+            .iter()
+            .fold(EvalTerm::default(), |total, (_, term)| total + term.get_net())
+    }
+
+    /// The blended value of the position for `color`, the same as
+    /// `Board::value_for` gives.
+    pub fn value(&self) -> f64 {
+        let value = self.total().blend(self.phase);
+        if self.color == WHITE { value } else { -value }
+    }
+}
+
+/// The two ranks in front of the square, from the color's side.
+#[inline]
+fn two_ranks_in_front(color: Color, index: usize) -> Bitboard {
+    let rank = index / 8;
+    let ranks = match color {
+        WHITE => 0xFFFF_u64.checked_shl(((rank + 1) * 8) as u32),
+        BLACK if rank >= 2 => Some(0xFFFF << ((rank - 2) * 8)),
+        BLACK => Some((1 << (rank * 8)) - 1),
+    };
+    ranks.unwrap_or(0)
+}
+
+/// The pawn shield and open file terms for the color's king.
+fn king_shelter(board: &Board, weights: &EvalWeights, color: Color) -> (EvalTerm, EvalTerm) {
+    let (mut shield, mut open_files) = (EvalTerm::default(), EvalTerm::default());
+    let king = match board.get_king_pos(color) {
+        Some(king) => square_index(king),
+        None => return (shield, open_files),
+    };
+    let bitboards = board.get_bitboards();
+    let all_pawns = bitboards.pawns(WHITE) | bitboards.pawns(BLACK);
+    let pawns = bitboards.pawns(color);
+    let shelter = pawns & (file(king) | adjacent_files(king)) & two_ranks_in_front(color, king);
+    shield = shield + weights.pawn_shield * shelter.count_ones() as f64;
+
+    let king_file = king % 8;
+    for file in (king_file.saturating_sub(1)..=(king_file + 1).min(7)).map(|file| FILE_A << file) {
+        if pawns & file == 0 {
+            open_files = open_files + weights.semi_open_file;
+            if all_pawns & file == 0 {
+                open_files = open_files + weights.open_file;
+            }
+        }
+    }
+    (shield, open_files)
+}
+
+/// The mobility of the color's pieces, and the pressure they put on the
+/// enemy king.
+fn piece_activity(board: &Board, weights: &EvalWeights, color: Color) -> (EvalTerm, EvalTerm) {
+    let bitboards = board.get_bitboards();
+    let occupied = bitboards.occupied();
+    let safe = !bitboards.color(color) & !pawn_attacks(!color, bitboards.pawns(!color));
+    let king_zone = board
+        .get_king_pos(!color)
+        .map_or(0, |king| piece_attacks(Piece::King(!color, king), occupied) | square_bit(king));
+
+    let mut mobility = EvalTerm::default();
+    let mut attackers = 0;
+    let mut attack = EvalTerm::default();
+    for pos in squares(bitboards.color(color)) {
+        let piece = match board.get_piece(pos) {
+            Some(piece) if !piece.is_king() && !piece.is_pawn() => piece,
+            _ => continue,
+        };
+        let attacks = piece_attacks(piece, occupied);
+        let kind = piece.kind_index();
+        mobility = mobility + weights.mobility[kind] * (attacks & safe).count_ones() as f64;
+        if attacks & king_zone != 0 {
+            attackers += 1;
+            attack = attack + weights.king_attack[kind] * (attacks & king_zone).count_ones() as f64;
+        }
+    }
+    let scale = weights.king_attackers[attackers.min(weights.king_attackers.len() - 1)];
+    (mobility, attack * scale)
+}
+
+/// The mobility term and the king safety terms for the color.
+pub(crate) fn mobility_and_king_safety(board: &Board, weights: &EvalWeights, color: Color) -> (EvalTerm, KingSafety) {
+    let (mobility, king_attacks) = piece_activity(board, weights, color);
+    let (pawn_shield, open_files) = king_shelter(board, weights, color);
+    (mobility, KingSafety { pawn_shield, open_files, king_attacks })
+}
+
+/// The number of entries in the pawn cache.
+const PAWN_CACHE_SIZE: usize = 1 << 14;
+
+/// A cache of the terms that depend on the pawns alone, indexed by the
+/// pawn hash mixed with the key of the pawn weights. Pawns move rarely, so
+/// most positions in a search share their pawn structure with many others.
+///
+/// Every search shares the one cache, whatever weights it uses, so entries
+/// are written without locks: each is stored with its key xored with its
+/// data, so an entry torn by two threads writing it at once reads as a
+/// miss.
+struct PawnCache {
+    entries: [[AtomicU64; 3]; PAWN_CACHE_SIZE],
+}
+
+static PAWN_CACHE: PawnCache = PawnCache {
+    entries: [const { [const { AtomicU64::new(0) }; 3] }; PAWN_CACHE_SIZE],
+};
+
+impl PawnCache {
+    #[inline]
+    fn entry(&self, key: u64) -> &[AtomicU64; 3] { &self.entries[(key % PAWN_CACHE_SIZE as u64) as usize] }
+
+    fn get(&self, key: u64) -> Option<(EvalTerm, Bitboard)> {
+        let [check, term, passed] = self.entry(key).each_ref().map(|word| word.load(Ordering::Relaxed));
+        if check ^ term ^ passed != key {
+            return None;
+        }
+        let middlegame = f32::from_bits((term >> 32) as u32) as f64;
+        let endgame = f32::from_bits(term as u32) as f64;
+        Some((EvalTerm::new(middlegame, endgame), passed))
+    }
+
+    fn insert(&self, key: u64, term: EvalTerm, passed: Bitboard) {
+        let term = ((term.middlegame as f32).to_bits() as u64) << 32 | (term.endgame as f32).to_bits() as u64;
+        let [check_word, term_word, passed_word] = self.entry(key);
+        check_word.store(key ^ term ^ passed, Ordering::Relaxed);
+        term_word.store(term, Ordering::Relaxed);
+        passed_word.store(passed, Ordering::Relaxed);
+    }
+}
+
+/// A key for a list of values, made with FNV-1a over their bits.
+fn hash_values(values: impl IntoIterator<Item = f64>) -> u64 {
+    values
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325, |key, value| (key ^ value.to_bits()).wrapping_mul(0x100_0000_01b3))
+}
+
+/// A key identifying the weights the pawn cache holds terms for. Unlike
+/// `Evaluator::get_key` it covers only the pawn weights, so it is cheap
+/// enough to work out for every position.
+#[inline]
+fn pawn_key(weights: &EvalWeights) -> u64 {
+    let terms = [weights.doubled_pawn, weights.isolated_pawn, weights.backward_pawn];
+    hash_values(terms.iter().flat_map(|term| [term.middlegame, term.endgame]))
+}
+
+/// The total pawn structure term, from white's point of view, looking up
+/// the pawn-only terms in the cache by the position's pawn hash and the
+/// weights.
+pub(crate) fn cached_pawn_structure(bitboards: &Bitboards, weights: &EvalWeights, pawn_hash: u64) -> EvalTerm {
+    let key = pawn_hash ^ pawn_key(weights);
+    let (term, passed) = PAWN_CACHE.get(key).unwrap_or_else(|| {
+        let (white, white_passed) = pawn_terms(bitboards, weights, WHITE);
+        let (black, black_passed) = pawn_terms(bitboards, weights, BLACK);
+        let term = white.total() - black.total();
+        PAWN_CACHE.insert(key, term, white_passed | black_passed);
+        (term, white_passed | black_passed)
+    });
+    term + passed_pawn_term(bitboards, weights, passed, WHITE) - passed_pawn_term(bitboards, weights, passed, BLACK)
+}
+
+/// Evaluates positions for the search, in place of `Board::value_for`.
+/// `EvalParams` evaluate positions the usual way, with their own values.
+pub trait Evaluator {
+    /// The value of the position for `color`, in tenths of a pawn.
+    fn evaluate(&self, board: &Board, color: Color) -> f64;
+
+    /// The base value of a piece, in tenths of a pawn, used to order
+    /// captures and to judge what they can gain.
+    fn get_material(&self, piece: &Piece) -> f64;
+
+    /// A key identifying the evaluator's values. It is mixed into the hash
+    /// of every position, so the values of different evaluators never
+    /// share an entry in a transposition table.
+    fn get_key(&self) -> u64;
+}
+
+impl Evaluator for EvalParams {
+    #[inline]
+    fn evaluate(&self, board: &Board, color: Color) -> f64 { board.value_with_params(color, self) }
+
+    #[inline]
+    fn get_material(&self, piece: &Piece) -> f64 { EvalParams::get_material(self, piece) }
+
+    fn get_key(&self) -> u64 {
+        let weights = &self.weights;
+        let terms = weights
+            .mobility
+            .iter()
+            .chain(&weights.king_attack)
+            .chain(&weights.passed_pawn)
+            .chain([&weights.pawn_shield, &weights.semi_open_file, &weights.open_file, &weights.tempo])
+            .chain([&weights.doubled_pawn, &weights.isolated_pawn, &weights.backward_pawn])
+            .flat_map(|term| [term.middlegame, term.endgame]);
+        let tables = self.middlegame_tables.iter().chain(&self.endgame_tables).flatten().flatten();
+        let scales = weights.king_attackers.into_iter().chain([weights.blocked_passed_pawn]);
+        let phase = self.phase.map(f64::from);
+        hash_values(self.material.iter().chain(tables).chain(&phase).copied().chain(terms).chain(scales))
+    }
+}
+
+/// A board that is valued by an `Evaluator` of its own, made with
+/// `Board::with_evaluator`. Searching it searches the board, valuing
+/// every position it reaches with the evaluator.
+pub struct EvaluatedBoard<'a, V: Evaluator> {
+    pub board: Board,
+    pub evaluator: &'a V,
+    /// The evaluator's key, worked out once for the whole search.
+    key: u64,
+}
+
+impl<'a, V: Evaluator> EvaluatedBoard<'a, V> {
+    #[inline]
+    pub(crate) fn new(board: Board, evaluator: &'a V) -> Self {
+        Self {
+            board,
+            evaluator,
+            key: evaluator.get_key(),
+        }
+    }
+
+    /// The same evaluator on another board.
+    #[inline]
+    fn with_board(&self, board: Board) -> Self {
+        Self {
+            board,
+            evaluator: self.evaluator,
+            key: self.key,
+        }
+    }
+}
+
+impl<V: Evaluator> Evaluate for EvaluatedBoard<'_, V> {
+    #[inline]
+    fn value_for(&self, color: Color) -> f64 { self.evaluator.evaluate(&self.board, color) }
+
+    #[inline]
+    fn get_current_player_color(&self) -> Color { self.board.get_current_player_color() }
+
+    #[inline]
+    fn get_legal_moves(&self) -> Vec<Move> { self.board.get_legal_moves() }
+
+    #[inline]
+    fn apply_eval_move(&self, m: Move) -> Self { self.with_board(self.board.apply_eval_move(m)) }
+
+    #[inline]
+    fn get_hash(&self) -> u64 { self.board.get_hash() ^ self.key }
+
+    #[inline]
+    fn is_current_player_in_check(&self) -> bool { self.board.is_current_player_in_check() }
+
+    fn apply_null_move(&self) -> Option<Self> { self.board.apply_null_move().map(|board| self.with_board(board)) }
+
+    #[inline]
+    fn get_noisy_moves(&self) -> Vec<Move> { self.board.get_noisy_moves() }
+
+    #[inline]
+    fn get_move_gain(&self, m: Move) -> f64 { search::material_gain(self, m).unwrap_or(0.0) }
+
+    #[inline]
+    fn get_material(&self, piece: &Piece) -> f64 { self.evaluator.get_material(piece) }
+
+    #[inline]
+    fn get_moving_piece(&self, m: Move) -> Option<Piece> { self.board.get_moving_piece(m) }
+
+    #[inline]
+    fn get_captured_piece(&self, m: Move) -> Option<Piece> { self.board.get_captured_piece(m) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_fen, Score, SearchConfig, SearchLimits, TranspositionTable};
+
+    const WEIGHTS: EvalWeights = EvalWeights::DEFAULT;
+
+    fn structure(fen: &str) -> PawnStructure { parse_fen(fen).unwrap().get_pawn_structure() }
+
+    #[test]
+    fn test_doubled_and_isolated() {
+        // the pawn in front is passed, the one behind it isn't
+        let pawns = structure("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1");
+        assert_eq!(pawns.doubled, WEIGHTS.doubled_pawn);
+        assert_eq!(pawns.isolated, WEIGHTS.isolated_pawn * 2.0);
+        assert_eq!(pawns.backward, EvalTerm::default());
+        assert_eq!(pawns.passed, WEIGHTS.passed_pawn[2]);
+
+        let pawns = structure("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1");
+        assert_eq!(pawns.total(), EvalTerm::default());
+    }
+
+    #[test]
+    fn test_backward() {
+        // d3 can't advance past e5's guard, and c4 can't drop back to help
+        let pawns = structure("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+        assert_eq!(pawns.backward, WEIGHTS.backward_pawn);
+        assert_eq!(pawns.isolated, -WEIGHTS.isolated_pawn);
+        assert_eq!(pawns.passed, WEIGHTS.passed_pawn[3]);
+
+        // without the guard, d3 is free to advance
+        let pawns = structure("4k3/8/8/8/2P5/3P4/8/4K3 w - - 0 1");
+        assert_eq!(pawns.backward, EvalTerm::default());
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        let free = structure("k7/8/8/8/4P3/8/8/4K3 w - - 0 1").passed;
+        let blocked = structure("8/8/8/4k3/4P3/8/8/4K3 w - - 0 1").passed;
+        let advanced = structure("k7/8/4P3/8/8/8/8/4K3 w - - 0 1").passed;
+        assert_eq!(free, WEIGHTS.passed_pawn[3]);
+        assert_eq!(blocked, free * WEIGHTS.blocked_passed_pawn);
+        assert!(advanced.endgame > free.endgame);
+
+        // black's passed pawns count against white, by their own rank
+        let black = structure("4k3/8/8/8/8/4p3/8/7K w - - 0 1").passed;
+        assert_eq!(black, -WEIGHTS.passed_pawn[5]);
+
+        // pawns facing each other on neighbouring files aren't passed
+        assert_eq!(structure("k7/8/3p4/8/4P3/8/8/7K w - - 0 1").passed, EvalTerm::default());
+    }
+
+    #[test]
+    fn test_pawn_cache() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1",
+        ];
+        for fen in fens {
+            let mut board = parse_fen(fen).unwrap();
+            for _ in 0..20 {
+                let expected = board.get_pawn_structure().total();
+                // once to fill the cache, and once to read it back
+                for _ in 0..2 {
+                    let cached = cached_pawn_structure(board.get_bitboards(), &WEIGHTS, board.pawn_hash());
+                    assert!((cached.middlegame - expected.middlegame).abs() < 1e-4, "{}", fen);
+                    assert!((cached.endgame - expected.endgame).abs() < 1e-4, "{}", fen);
+                }
+                match board.get_legal_moves().first() {
+                    Some(m) => board = board.apply_eval_move(*m),
+                    None => break,
+                }
+            }
+        }
+
+        // positions with the same pawns share an entry
+        let start = Board::default();
+        let moved = start.apply_eval_move(crate::Move::Piece(crate::G1, crate::F3));
+        assert_eq!(start.pawn_hash(), moved.pawn_hash());
+        assert_ne!(start.hash(), moved.hash());
+
+        // but other weights don't read the default weights' entry
+        let board = parse_fen(fens[3]).unwrap();
+        let bitboards = board.get_bitboards();
+        let doubled = EvalWeights {
+            isolated_pawn: WEIGHTS.isolated_pawn * 2.0,
+            ..WEIGHTS
+        };
+        for weights in [&WEIGHTS, &doubled, &WEIGHTS] {
+            let cached = cached_pawn_structure(bitboards, weights, board.pawn_hash());
+            let expected = (pawn_structure(bitboards, weights, WHITE) - pawn_structure(bitboards, weights, BLACK)).total();
+            assert!((cached.middlegame - expected.middlegame).abs() < 1e-4);
+            assert!((cached.endgame - expected.endgame).abs() < 1e-4);
+        }
+    }
+
+    fn activity(fen: &str) -> (EvalTerm, KingSafety) {
+        let board = parse_fen(fen).unwrap();
+        let (white_mobility, white_safety) = mobility_and_king_safety(&board, &EvalWeights::DEFAULT, WHITE);
+        let (black_mobility, black_safety) = mobility_and_king_safety(&board, &EvalWeights::DEFAULT, BLACK);
+        (white_mobility - black_mobility, white_safety - black_safety)
+    }
+
+    #[test]
+    fn test_mobility() {
+        let knight = EvalWeights::DEFAULT.mobility[Piece::Knight(WHITE, crate::A1).kind_index()];
+        assert_eq!(activity("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").0, knight * 8.0);
+        assert_eq!(activity("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").0, knight * 2.0);
+        // squares guarded by enemy pawns don't count, but captures do
+        assert_eq!(activity("4k3/8/4p3/8/3N4/8/8/4K3 w - - 0 1").0, knight * 7.0);
+        // nor do squares held by the knight's own side
+        assert_eq!(activity("4k3/8/8/8/3N4/8/4P3/4K3 w - - 0 1").0, knight * 7.0);
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let weights = EvalWeights::DEFAULT;
+        assert_eq!(activity("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").1.total(), EvalTerm::default());
+
+        // without its pawns, white's king has no shield and half-open files
+        let safety = activity("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1").1;
+        assert_eq!(safety.pawn_shield, -(weights.pawn_shield * 3.0));
+        assert_eq!(safety.open_files, weights.semi_open_file * 3.0);
+        let safety = activity("6k1/8/8/8/8/8/8/6K1 w - - 0 1").1;
+        assert_eq!(safety.open_files, EvalTerm::default());
+        let safety = activity("6k1/8/8/8/8/8/8/K7 w - - 0 1").1;
+        assert_eq!(safety.open_files, -((weights.semi_open_file + weights.open_file) * 1.0));
+
+        // the queen and knight both hit f7 and h7
+        let safety = activity("6k1/5ppp/8/6NQ/8/8/5PPP/6K1 w - - 0 1").1;
+        let queen = weights.king_attack[Piece::Queen(WHITE, crate::A1).kind_index()];
+        let knight = weights.king_attack[Piece::Knight(WHITE, crate::A1).kind_index()];
+        assert_eq!(safety.king_attacks, (queen * 2.0 + knight * 2.0) * weights.king_attackers[2]);
+        // a lone attacker isn't counted
+        let safety = activity("6k1/5ppp/8/7Q/8/8/5PPP/6K1 w - - 0 1").1;
+        assert_eq!(safety.king_attacks, EvalTerm::default());
+    }
+
+    #[test]
+    fn test_eval_weights() {
+        let board = parse_fen("rnbqkbnr/pppppppp/8/8/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.value_for(WHITE), board.value_with_params(WHITE, &EvalParams::default()));
+        assert_eq!(board.value_for(BLACK), -board.value_for(WHITE));
+
+        // only white has developed, so mobility counts for white
+        let params = EvalParams {
+            weights: EvalWeights {
+                mobility: [EvalTerm::default(); 6],
+                ..EvalWeights::DEFAULT
+            },
+            ..EvalParams::DEFAULT
+        };
+        assert!(board.value_with_params(WHITE, &params) < board.value_for(WHITE));
+    }
+
+    #[test]
+    fn test_evaluator() {
+        let board = parse_fen("4k3/8/8/2n1b3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let search = |board: &EvaluatedBoard<EvalParams>| {
+            board.search_with_config(&SearchLimits::depth(3), SearchConfig::plain(), &mut TranspositionTable::new(1), &mut || false)
+        };
+
+        let params = EvalParams::DEFAULT;
+        let expected = board.search_with_config(&SearchLimits::depth(3), SearchConfig::plain(), &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(search(&board.with_evaluator(&params)), expected);
+
+        // the pawn takes whichever piece is worth more
+        let mut params = EvalParams::DEFAULT;
+        params.material[Piece::Knight(WHITE, crate::A1).kind_index()] = 0.0;
+        assert_eq!(search(&board.with_evaluator(&params)).best_move, crate::Move::Piece(crate::D4, crate::E5));
+        let mut params = EvalParams::DEFAULT;
+        params.material[Piece::Bishop(WHITE, crate::A1).kind_index()] = 0.0;
+        assert_eq!(search(&board.with_evaluator(&params)).best_move, crate::Move::Piece(crate::D4, crate::C5));
+    }
+
+    #[test]
+    fn test_evaluator_material() {
+        let board = parse_fen("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let mut params = EvalParams::DEFAULT;
+        params.material[Piece::Knight(WHITE, crate::A1).kind_index()] = 100.0;
+        let evaluated = board.with_evaluator(&params);
+        let capture = crate::Move::Piece(crate::E4, crate::D5);
+        assert_eq!(evaluated.get_move_gain(capture), 100.0);
+
+        // taking the knight is only worth searching at its raised value
+        let stand_pat = Score::from_value(evaluated.value_for(WHITE));
+        let alpha = stand_pat + Score::from_value(60.0);
+        let mut stats = crate::SearchStats::default();
+        assert!(evaluated.quiescence(alpha, Score::INFINITE, 0, &mut stats) > alpha);
+        assert_eq!(board.get_move_gain(capture), 30.0);
+
+        // the key keeps each evaluator's positions apart, move after move
+        let default = EvalParams::DEFAULT;
+        assert_ne!(evaluated.get_hash(), board.with_evaluator(&default).get_hash());
+        let same = params;
+        assert_eq!(evaluated.get_hash(), board.with_evaluator(&same).get_hash());
+        assert_eq!(
+            evaluated.apply_eval_move(capture).get_hash(),
+            board.apply_eval_move(capture).with_evaluator(&params).get_hash()
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use alloc::string::String;
+use alloc::vec::Vec;
 
 use crate::board::Board;
-use crate::util::{format_fen, parse_fen, parse_san_move};
-use crate::{Color, GameResult};
+use crate::util::{format_fen, parse_fen, parse_san_move, to_san};
+use crate::{Color, Evaluate, GameResult, Move};
 
 pub enum GameAction {
     AcceptDraw,
+    ClaimDraw,
     MakeMove(String),
     OfferDraw(String),
+    /// Hand the move to the other player without moving a piece.
+    Pass,
     Resign,
 }
 
@@ -23,6 +27,8 @@ pub enum GameError {
     GameAlreadyOver,
     InvalidMove,
     InvalidPosition,
+    NoMoveToUndo,
+    NoMoveToRedo,
 }
 
 #[derive(Debug, PartialEq)]
@@ -33,6 +39,35 @@ pub enum GameOver {
     BlackResigns,
     Stalemate,
     DrawAccepted,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+}
+
+/// The number of times a position must occur before a draw can be claimed.
+const CLAIMABLE_REPETITIONS: usize = 3;
+
+/// The number of times a position must occur to end the game in a draw.
+const AUTOMATIC_REPETITIONS: usize = 5;
+
+/// The plies without a capture or pawn move before a draw can be claimed.
+const CLAIMABLE_HALFMOVES: u32 = 100;
+
+/// The plies without a capture or pawn move that end the game in a draw.
+const AUTOMATIC_HALFMOVES: u32 = 150;
+
+/// A single move made during a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ply {
+    /// The move played, or `None` when the player passed.
+    pub chess_move: Option<Move>,
+    /// The move in standard algebraic notation, or `--` for a pass.
+    pub san: String,
+    /// The board before the move was made.
+    pub board: Board,
+    /// The player offering a draw along with the move, if any.
+    pub draw_offered: Option<Color>,
 }
 
 #[derive(Default)]
@@ -40,6 +75,9 @@ pub struct Game {
     pub board: Board,
     pub draw_offered: Option<Color>,
     pub status: Option<GameOver>,
+    starting_board: Board,
+    history: Vec<Ply>,
+    undone: Vec<Ply>,
 }
 
 impl Game {
@@ -58,11 +96,83 @@ impl Game {
             board,
             draw_offered,
             status,
+            starting_board: board,
+            history: Vec::new(),
+            undone: Vec::new(),
         })
     }
 
-    pub fn to_fen(&self, halfmove_clock: u8, fullmove_number: u8) -> Result<String, String> {
-        format_fen(&self.board, halfmove_clock, fullmove_number)
+    /// The position the game was started from.
+    pub fn get_starting_board(&self) -> Board {
+        self.starting_board
+    }
+
+    /// Every ply played so far, oldest first.
+    pub fn history(&self) -> &[Ply] {
+        &self.history
+    }
+
+    /// The board after the given number of plies, where ply 0 is the
+    /// starting position.
+    pub fn board_at(&self, ply: usize) -> Option<Board> {
+        match ply.cmp(&self.history.len()) {
+            core::cmp::Ordering::Less => Some(self.history[ply].board),
+            core::cmp::Ordering::Equal => Some(self.board),
+            core::cmp::Ordering::Greater => None,
+        }
+    }
+
+    /// How many times the current position has occurred, including now.
+    pub fn repetition_count(&self) -> usize {
+        // nothing before the last capture or pawn move can repeat
+        1 + self
+            .history
+            .iter()
+            .rev()
+            .take(self.board.get_halfmove_clock() as usize)
+            .filter(|ply| ply.board.is_same_position(&self.board))
+            .count()
+    }
+
+    /// Take back the last ply. This also reopens a game that has ended.
+    /// A game ended by a resignation or a draw agreed or claimed is only
+    /// reopened, since no ply ended it.
+    pub fn undo(&mut self) -> Result<(), GameError> {
+        if matches!(
+            self.status,
+            Some(
+                GameOver::WhiteResigns
+                    | GameOver::BlackResigns
+                    | GameOver::DrawAccepted
+                    | GameOver::ThreefoldRepetition
+                    | GameOver::FiftyMoveRule
+            )
+        ) {
+            self.status = None;
+            return Ok(());
+        }
+        let ply = self.history.pop().ok_or(GameError::NoMoveToUndo)?;
+        self.board = ply.board;
+        self.draw_offered = self.history.last().and_then(|ply| ply.draw_offered);
+        self.status = None;
+        self.undone.push(ply);
+        Ok(())
+    }
+
+    /// Replay the last ply taken back with `undo`.
+    pub fn redo(&mut self) -> Result<&Option<GameOver>, GameError> {
+        if self.status.is_some() {
+            return Err(GameError::GameAlreadyOver {});
+        }
+        let ply = self.undone.pop().ok_or(GameError::NoMoveToRedo)?;
+        match ply.chess_move {
+            Some(chess_move) => self.play(chess_move, ply.draw_offered.is_some()),
+            None => self.pass(),
+        }
+    }
+
+    pub fn to_fen(&self) -> Result<String, String> {
+        format_fen(&self.board)
     }
 
     pub fn get_turn_color(&self) -> Color {
@@ -75,10 +185,15 @@ impl Game {
         }
         match action {
             GameAction::AcceptDraw => self.accept_draw(),
+            GameAction::ClaimDraw => self.claim_draw(),
             GameAction::MakeMove(move_str) => self.move_piece(move_str, false),
             GameAction::OfferDraw(move_str) => self.move_piece(move_str, true),
+            GameAction::Pass => self.pass(),
             GameAction::Resign => self.resign(),
-        }
+        }?;
+        // a new action replaces whatever was taken back
+        self.undone.clear();
+        Ok(&self.status)
     }
 
     fn accept_draw(&mut self) -> Result<&Option<GameOver>, GameError> {
@@ -91,6 +206,17 @@ impl Game {
         Err(GameError::InvalidMove {})
     }
 
+    fn claim_draw(&mut self) -> Result<&Option<GameOver>, GameError> {
+        if self.repetition_count() >= CLAIMABLE_REPETITIONS {
+            self.status = Some(GameOver::ThreefoldRepetition);
+            return Ok(&self.status);
+        } else if self.board.get_halfmove_clock() >= CLAIMABLE_HALFMOVES {
+            self.status = Some(GameOver::FiftyMoveRule);
+            return Ok(&self.status);
+        }
+        Err(GameError::InvalidMove {})
+    }
+
     fn move_piece(
         &mut self,
         movestr: &str,
@@ -102,16 +228,18 @@ impl Game {
                 return Err(GameError::InvalidMove {});
             }
         };
+        self.play(chess_move, draw_offered)
+    }
 
-        self.draw_offered = match draw_offered {
-            true => Some(self.get_turn_color()),
-            false => None,
-        };
-        self.status = match self.board.play_move(chess_move) {
-            GameResult::Continuing(board) => {
-                self.board = board;
-                None
-            }
+    fn play(
+        &mut self,
+        chess_move: Move,
+        draw_offered: bool,
+    ) -> Result<&Option<GameOver>, GameError> {
+        let color = self.get_turn_color();
+        let san = to_san(&self.board, chess_move);
+        let status = match self.board.play_move(chess_move) {
+            GameResult::Continuing(_) => None,
             GameResult::IllegalMove(_) => {
                 return Err(GameError::InvalidMove {});
             }
@@ -121,6 +249,41 @@ impl Game {
                 Color::White => Some(GameOver::WhiteCheckmates),
             },
         };
+
+        let draw_offered = match draw_offered {
+            true => Some(color),
+            false => None,
+        };
+        self.history.push(Ply {
+            chess_move: Some(chess_move),
+            san,
+            board: self.board,
+            draw_offered,
+        });
+        self.board = self.board.apply_eval_move(chess_move);
+        self.draw_offered = draw_offered;
+        self.status = status;
+        if self.status.is_none() && self.repetition_count() >= AUTOMATIC_REPETITIONS {
+            self.status = Some(GameOver::FivefoldRepetition);
+        } else if self.status.is_none() && self.board.get_halfmove_clock() >= AUTOMATIC_HALFMOVES {
+            self.status = Some(GameOver::SeventyFiveMoveRule);
+        }
+        Ok(&self.status)
+    }
+
+    fn pass(&mut self) -> Result<&Option<GameOver>, GameError> {
+        // passing out of check would leave the king en prise
+        if self.board.is_in_check(self.get_turn_color()) {
+            return Err(GameError::InvalidMove {});
+        }
+        self.history.push(Ply {
+            chess_move: None,
+            san: String::from("--"),
+            board: self.board,
+            draw_offered: None,
+        });
+        self.board = self.board.pass_turn();
+        self.draw_offered = None;
         Ok(&self.status)
     }
 
@@ -179,4 +342,261 @@ mod tests {
             Some(Piece::Rook(Color::White, Position::pgn("c8").unwrap()))
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::default();
+        assert_eq!(game.undo(), Err(GameError::NoMoveToUndo));
+        for game_move in ["e4", "e5", "Nf3"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(game.history().len(), 3);
+        assert_eq!(game.history()[2].san, "Nf3");
+        let after_nf3 = game.board;
+
+        game.undo().expect("undo Nf3");
+        game.undo().expect("undo e5");
+        assert_eq!(game.history().len(), 1);
+        assert_eq!(game.get_turn_color(), Color::Black);
+        assert_eq!(Some(game.board), game.board_at(1));
+
+        game.redo().expect("redo e5");
+        game.redo().expect("redo Nf3");
+        assert_eq!(game.board, after_nf3);
+        assert_eq!(game.redo(), Err(GameError::NoMoveToRedo));
+
+        // a new move replaces the moves taken back
+        game.undo().expect("undo Nf3");
+        game.make_move(&GameAction::from("Nc3")).expect("Nc3");
+        assert_eq!(game.redo(), Err(GameError::NoMoveToRedo));
+
+        assert_eq!(game.board_at(0), Some(Board::default()));
+        assert_eq!(game.board_at(3), Some(game.board));
+        assert_eq!(game.board_at(4), None);
+
+        for _ in 0..3 {
+            game.undo().expect("undo");
+        }
+        assert_eq!(game.board, Board::default());
+        assert_eq!(game.undo(), Err(GameError::NoMoveToUndo));
+    }
+
+    #[test]
+    fn test_redo_after_game_over() {
+        let mut game = Game::default();
+        for game_move in ["e4", "e5"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        game.undo().expect("undo e5");
+        game.make_move(&GameAction::Resign).expect("resign");
+        assert_eq!(game.status, Some(GameOver::BlackResigns));
+
+        // resigning replaces the move taken back, and ends the game
+        assert_eq!(game.redo(), Err(GameError::GameAlreadyOver));
+        assert_eq!(game.status, Some(GameOver::BlackResigns));
+        assert_eq!(game.history().len(), 1);
+
+        game.undo().expect("undo resign");
+        assert_eq!(game.redo(), Err(GameError::NoMoveToRedo));
+        game.undo().expect("undo e4");
+        assert_eq!(game.redo(), Ok(&None));
+        assert_eq!(game.redo(), Err(GameError::NoMoveToRedo));
+    }
+
+    #[test]
+    fn test_undo_game_ending_action() {
+        let mut game = Game::default();
+        game.make_move(&GameAction::OfferDraw(String::from("e4")))
+            .expect("e4");
+        let after_e4 = game.board;
+
+        // only the result is taken back, not the move before it
+        game.make_move(&GameAction::Resign).expect("resign");
+        game.undo().expect("undo resign");
+        assert_eq!(game.status, None);
+        assert_eq!(game.board, after_e4);
+        assert_eq!(game.history().len(), 1);
+
+        game.make_move(&GameAction::AcceptDraw).expect("accept draw");
+        assert_eq!(game.status, Some(GameOver::DrawAccepted));
+        game.undo().expect("undo draw");
+        assert_eq!(game.status, None);
+        assert_eq!(game.board, after_e4);
+
+        for game_move in ["Nf6", "Nf3", "Ng8", "Ng1", "Nf6", "Nf3", "Ng8", "Ng1"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        let repeated = game.board;
+        game.make_move(&GameAction::ClaimDraw).expect("claim draw");
+        game.undo().expect("undo claim");
+        assert_eq!(game.status, None);
+        assert_eq!(game.board, repeated);
+        assert_eq!(game.history().len(), 9);
+    }
+
+    #[test]
+    fn test_pass() {
+        let mut game = Game::default();
+        game.make_move(&GameAction::from("e4")).expect("e4");
+        let after_e4 = game.board;
+        game.make_move(&GameAction::Pass).expect("pass");
+        assert_eq!(game.get_turn_color(), Color::White);
+        assert_eq!(game.board.get_en_passant(), None);
+        assert_eq!(game.history()[1].san, "--");
+        assert_eq!(game.history()[1].chess_move, None);
+
+        game.undo().expect("undo pass");
+        assert_eq!(game.board, after_e4);
+        game.redo().expect("redo pass");
+        assert_eq!(game.get_turn_color(), Color::White);
+        assert_eq!(game.board_at(2), Some(game.board));
+
+        // passing out of check is illegal
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K2r w - - 0 1", None, None).unwrap();
+        assert_eq!(game.make_move(&GameAction::Pass), Err(GameError::InvalidMove));
+    }
+
+    #[test]
+    fn test_undo_game_over() {
+        let mut game = Game::default();
+        game.make_move(&GameAction::OfferDraw(String::from("f3")))
+            .expect("f3");
+        for game_move in ["e5", "g4", "Qh4"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(game.status, Some(GameOver::BlackCheckmates));
+        assert!(game.board.is_checkmate());
+
+        game.undo().expect("undo Qh4");
+        assert_eq!(game.status, None);
+        game.undo().expect("undo g4");
+        game.undo().expect("undo e5");
+        assert_eq!(game.draw_offered, Some(Color::White));
+        assert_eq!(game.history()[0].draw_offered, Some(Color::White));
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game = Game::default();
+        for game_move in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(
+            game.make_move(&GameAction::ClaimDraw),
+            Err(GameError::InvalidMove)
+        );
+
+        game.make_move(&GameAction::from("Ng8")).expect("Ng8");
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.status, None);
+        assert_eq!(
+            game.make_move(&GameAction::ClaimDraw),
+            Ok(&Some(GameOver::ThreefoldRepetition))
+        );
+    }
+
+    #[test]
+    fn test_fivefold_repetition() {
+        let mut game = Game::default();
+        for _ in 0..3 {
+            for game_move in ["Nc3", "Nc6", "Nb1", "Nb8"] {
+                game.make_move(&GameAction::from(game_move))
+                    .expect(game_move);
+            }
+        }
+        assert_eq!(game.status, None);
+        for game_move in ["Nc3", "Nc6", "Nb1"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(
+            game.make_move(&GameAction::from("Nb8")),
+            Ok(&Some(GameOver::FivefoldRepetition))
+        );
+    }
+
+    #[test]
+    fn test_repetition_en_passant() {
+        // the first e4 allows no en passant capture, so it repeats later
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", None, None).unwrap();
+        for game_move in ["e4", "Kd7", "Kd2", "Ke8", "Ke1", "Kd7", "Kd2", "Ke8", "Ke1"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(game.repetition_count(), 3);
+
+        // here d4 can be taken en passant, so the position never repeats
+        let mut game =
+            Game::from_fen("4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1", None, None).unwrap();
+        for game_move in ["d4", "Kd7", "Kd2", "Ke8", "Ke1", "Kd7", "Kd2", "Ke8", "Ke1"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(game.repetition_count(), 2);
+    }
+
+    #[test]
+    fn test_move_counters() {
+        let mut game = Game::default();
+        for game_move in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        assert_eq!(
+            game.to_fen().unwrap(),
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3"
+        );
+        game.make_move(&GameAction::from("a6")).expect("a6");
+        assert_eq!(game.board.get_halfmove_clock(), 0);
+        game.make_move(&GameAction::from("Bxc6")).expect("Bxc6");
+        assert_eq!(game.board.get_halfmove_clock(), 0);
+        assert_eq!(game.board.get_fullmove_number(), 4);
+        game.undo().expect("undo Bxc6");
+        assert_eq!(game.board.get_halfmove_clock(), 0);
+        assert_eq!(game.board.get_fullmove_number(), 4);
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 98 80", None, None).unwrap();
+        game.make_move(&GameAction::from("Kd1")).expect("Kd1");
+        assert_eq!(
+            game.make_move(&GameAction::ClaimDraw),
+            Err(GameError::InvalidMove)
+        );
+        game.make_move(&GameAction::from("Kd8")).expect("Kd8");
+        assert_eq!(game.status, None);
+        assert_eq!(
+            game.make_move(&GameAction::ClaimDraw),
+            Ok(&Some(GameOver::FiftyMoveRule))
+        );
+
+        // a pawn move resets the count
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 99 80", None, None).unwrap();
+        game.make_move(&GameAction::from("e3")).expect("e3");
+        assert_eq!(
+            game.make_move(&GameAction::ClaimDraw),
+            Err(GameError::InvalidMove)
+        );
+    }
+
+    #[test]
+    fn test_seventy_five_move_rule() {
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 148 100", None, None).unwrap();
+        game.make_move(&GameAction::from("Kd1")).expect("Kd1");
+        assert_eq!(game.status, None);
+        assert_eq!(
+            game.make_move(&GameAction::from("Kd8")),
+            Ok(&Some(GameOver::SeventyFiveMoveRule))
+        );
+    }
+}
@@ -8,11 +8,22 @@ use alloc::{
 
 use core::convert::TryFrom;
 
+mod bitboard;
+
 mod board;
-pub use board::{Board, BoardBuilder};
+pub use board::{Board, BoardBuilder, MAX_PHASE};
+
+mod eval;
+pub use eval::{EvalTerm, EvalTrace, EvalWeights, EvaluatedBoard, Evaluator, KingSafety, PawnStructure, TraceTerm};
 
 mod game;
-pub use game::{Game, GameAction, GameError, GameOver};
+pub use game::{Game, GameAction, GameError, GameOver, Ply};
+
+mod params;
+pub use params::EvalParams;
+
+mod pgn;
+pub use pgn::{parse_pgn, Pgn, PgnError, PgnMove};
 
 mod square;
 pub use square::{Square, EMPTY_SQUARE};
@@ -23,12 +34,24 @@ pub use piece::Piece;
 mod position;
 pub use position::*;
 
+mod score;
+pub use score::Score;
+
+mod search;
+pub use search::{
+    Bound, SearchConfig, SearchControl, SearchLimits, SearchResult, SearchStats, TableEntry,
+    TranspositionTable, DEFAULT_DEPTH, DEFAULT_TABLE_SIZE_MB, MAX_DEPTH,
+};
+
 mod util;
 pub use util::*;
 
+mod zobrist;
+
 pub const WHITE: Color = Color::White;
 pub const BLACK: Color = Color::Black;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum GameResult {
     Continuing(Board),
@@ -117,6 +140,63 @@ impl Move {
     pub fn parse(repr: String) -> Result<Self, String> {
         Self::try_from(repr)
     }
+
+    /// Parse a move in long algebraic (UCI) notation, such as `e2e4` or `e7e8q`.
+    /// Castling is written as the king's move, e.g. `e1g1`. The null move
+    /// `0000` can't be played, so it is an error.
+    pub fn from_uci(board: &Board, repr: &str) -> Result<Self, String> {
+        let repr = repr.trim();
+        if repr == "0000" {
+            return Err(String::from("the null move `0000` can't be played"));
+        } else if !repr.is_ascii() || repr.len() < 4 || repr.len() > 5 {
+            return Err(format!("invalid uci move `{}`", repr));
+        }
+
+        let from = Position::pgn(&repr[..2])?;
+        let to = Position::pgn(&repr[2..4])?;
+
+        if let Some(Piece::King(color, pos)) = board.get_piece(from) {
+            if pos == Position::king_pos(color) && to == pos.next_right().next_right() {
+                return Ok(Self::KingSideCastle);
+            } else if pos == Position::king_pos(color) && to == pos.next_left().next_left() {
+                return Ok(Self::QueenSideCastle);
+            }
+        }
+
+        let color = board.get_turn_color();
+        let offboard = Position::new(-1, -1);
+        Ok(match repr.chars().nth(4) {
+            None => Self::Piece(from, to),
+            Some('q') => Self::Promotion(from, to, Piece::Queen(color, offboard)),
+            Some('r') => Self::Promotion(from, to, Piece::Rook(color, offboard)),
+            Some('b') => Self::Promotion(from, to, Piece::Bishop(color, offboard)),
+            Some('n') => Self::Promotion(from, to, Piece::Knight(color, offboard)),
+            Some(c) => return Err(format!("invalid promotion `{}`", c)),
+        })
+    }
+
+    /// Format a move in long algebraic (UCI) notation. A resignation is
+    /// written as the null move `0000`.
+    pub fn to_uci(&self, board: &Board) -> String {
+        let king_pos = Position::king_pos(board.get_turn_color());
+        match self {
+            Self::Piece(from, to) => format!("{}{}", from, to),
+            Self::Promotion(from, to, piece) => {
+                let suffix = match piece {
+                    Piece::Queen(_, _) => 'q',
+                    Piece::Rook(_, _) => 'r',
+                    Piece::Bishop(_, _) => 'b',
+                    Piece::Knight(_, _) => 'n',
+                    Piece::King(_, _) => 'k',
+                    Piece::Pawn(_, _) => 'p',
+                };
+                format!("{}{}{}", from, to, suffix)
+            }
+            Self::KingSideCastle => format!("{}{}", king_pos, king_pos.next_right().next_right()),
+            Self::QueenSideCastle => format!("{}{}", king_pos, king_pos.next_left().next_left()),
+            Self::Resign => String::from("0000"),
+        }
+    }
 }
 
 impl core::fmt::Display for Move {
@@ -133,6 +213,11 @@ impl core::fmt::Display for Move {
     }
 }
 
+/// How much more than its estimated gain a capture must be able to win
+/// before quiescence search bothers with it, in case the position improves
+/// in other ways as well.
+pub const DELTA_MARGIN: f64 = 20.0;
+
 pub trait Evaluate: Sized {
     fn value_for(&self, color: Color) -> f64;
 
@@ -142,35 +227,150 @@ pub trait Evaluate: Sized {
 
     fn apply_eval_move(&self, m: Move) -> Self;
 
-    fn get_best_next_move(&self, depth: i32) -> (Move, u64, f64) {
-        let legal_moves = self.get_legal_moves();
-        let mut best_move_value = -999999.0;
-        let mut best_move = Move::Resign;
+    /// A hash identifying the position, used to find it in a
+    /// transposition table.
+    fn get_hash(&self) -> u64;
+
+    /// Is the player to move in check? A position without legal moves is
+    /// checkmate if so, and stalemate otherwise. Mate detection and check
+    /// extensions need it: without it, every position without legal moves
+    /// counts as a stalemate and checks are never extended.
+    fn is_current_player_in_check(&self) -> bool { false }
+
+    /// Pass the turn to the other player without moving, for null-move
+    /// pruning, or `None` if the player to move might be in zugzwang, where
+    /// passing would be better than any move they could make.
+    fn apply_null_move(&self) -> Option<Self> { None }
+
+    /// The legal captures and promotions, which `quiescence` keeps
+    /// searching after the depth runs out. Without them, the search stops
+    /// at the value of the position itself.
+    fn get_noisy_moves(&self) -> Vec<Move> { Vec::new() }
+
+    /// The most the player to move can hope to gain from a noisy move, in
+    /// the units of `value_for`. Quiescence search skips the moves that
+    /// can't raise the value enough to matter.
+    fn get_move_gain(&self, _m: Move) -> f64 { f64::INFINITY }
+
+    /// The base value of a piece, in the units of `value_for`, used to
+    /// order captures and to judge what they can gain.
+    fn get_material(&self, piece: &Piece) -> f64 { EvalParams::DEFAULT.get_material(piece) }
+
+    /// The piece making a move, used to try captures by the least valuable
+    /// attacker first.
+    fn get_moving_piece(&self, _m: Move) -> Option<Piece> { None }
+
+    /// The piece a move captures, used to try captures of the most valuable
+    /// victim first. Without it, only promotions are ordered before other
+    /// moves.
+    fn get_captured_piece(&self, _m: Move) -> Option<Piece> { None }
+
+    /// The value of the position for the player to move, after playing out
+    /// the noisy moves, so a capture just before the end of the search is
+    /// never mistaken for a win. The player may always "stand pat" rather
+    /// than make a capture, unless they are in check, when every move is
+    /// searched so checkmate is still found.
+    ///
+    /// `ply` is the distance from the root, recorded as the selective depth
+    /// in `stats`.
+    fn quiescence(&self, mut alpha: Score, beta: Score, ply: usize, stats: &mut SearchStats) -> Score {
+        stats.seldepth = stats.seldepth.max(ply);
+        let in_check = self.is_current_player_in_check();
+        let stand_pat = Score::from_value(self.value_for(self.get_current_player_color()));
+        if !in_check {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            if stand_pat > alpha {
+                alpha = stand_pat;
+            }
+        }
 
-        let color = self.get_current_player_color();
+        let mut moves = if in_check { self.get_legal_moves() } else { self.get_noisy_moves() };
+        if in_check && moves.is_empty() {
+            return Score::mated_in(ply as i32);
+        }
+        search::order_captures(self, &mut moves);
+        for m in moves {
+            // delta pruning
+            if !in_check && stand_pat + Score::from_value(self.get_move_gain(m) + DELTA_MARGIN) <= alpha {
+                continue;
+            }
 
-        let mut board_count = 0;
-        for m in &legal_moves {
-            let child_board_value = self.apply_eval_move(*m).minimax(
-                depth,
-                -1000000.0,
-                1000000.0,
-                false,
-                color,
-                &mut board_count,
-            );
-            if child_board_value >= best_move_value {
-                best_move = *m;
-                best_move_value = child_board_value;
+            stats.board_count += 1;
+            let child_board_value = -self.apply_eval_move(m).quiescence(-beta, -alpha, ply + 1, stats);
+            if child_board_value >= beta {
+                return child_board_value;
+            }
+            if child_board_value > alpha {
+                alpha = child_board_value;
             }
         }
 
-        (best_move, board_count, best_move_value)
+        alpha
+    }
+
+    fn get_best_next_move(&self, depth: i32) -> (Move, u64, Score) {
+        let mut table = TranspositionTable::for_depth(depth);
+        let (best_move, stats, best_move_value) = self.get_best_next_move_with_table(depth, &mut table);
+        (best_move, stats.board_count, best_move_value)
+    }
+
+    /// Find the best move like `get_best_next_move`, reusing the positions
+    /// stored in `table` by earlier searches.
+    fn get_best_next_move_with_table(
+        &self,
+        depth: i32,
+        table: &mut TranspositionTable,
+    ) -> (Move, SearchStats, Score) {
+        let mut never_stop = || false;
+        let mut searcher = search::Searcher::new(table, &mut never_stop, None, SearchConfig::default());
+        let (best_move, best_move_value) = searcher
+            .search_root(self, depth + 1, -Score::INFINITE, Score::INFINITE, &[])
+            .unwrap_or((Move::Resign, Score::DRAW));
+        (best_move, searcher.stats, best_move_value)
+    }
+
+    /// Search one ply deeper at a time until one of the `limits` is reached
+    /// or `control` stops the search, returning the best move of the last
+    /// completed iteration.
+    fn search<C: SearchControl>(
+        &self,
+        limits: &SearchLimits,
+        table: &mut TranspositionTable,
+        control: &mut C,
+    ) -> SearchResult {
+        self.search_with_config(limits, SearchConfig::default(), table, control)
+    }
+
+    /// Search like `search`, with only the refinements enabled in `config`.
+    fn search_with_config<C: SearchControl>(
+        &self,
+        limits: &SearchLimits,
+        config: SearchConfig,
+        table: &mut TranspositionTable,
+        control: &mut C,
+    ) -> SearchResult {
+        self.search_multipv(limits, config, 1, table, control).remove(0)
+    }
+
+    /// Search like `search_with_config`, returning the best `lines` moves
+    /// with their values and expected lines of play, best first. There are
+    /// fewer when there aren't enough legal moves.
+    fn search_multipv<C: SearchControl>(
+        &self,
+        limits: &SearchLimits,
+        config: SearchConfig,
+        lines: usize,
+        table: &mut TranspositionTable,
+        control: &mut C,
+    ) -> Vec<SearchResult> {
+        search::iterative_deepening(self, limits, config, lines, table, control)
     }
 
-    fn get_worst_next_move(&self, depth: i32) -> (Move, u64, f64) {
+    fn get_worst_next_move(&self, depth: i32) -> (Move, u64, Score) {
         let legal_moves = self.get_legal_moves();
-        let mut best_move_value = -999999.0;
+        let mut best_move_value = -Score::INFINITE;
         let mut best_move = Move::Resign;
 
         let color = self.get_current_player_color();
@@ -179,8 +379,8 @@ pub trait Evaluate: Sized {
         for m in &legal_moves {
             let child_board_value = self.apply_eval_move(*m).minimax(
                 depth,
-                -1000000.0,
-                1000000.0,
+                -Score::INFINITE,
+                Score::INFINITE,
                 true,
                 !color,
                 &mut board_count,
@@ -195,36 +395,58 @@ pub trait Evaluate: Sized {
         (best_move, board_count, best_move_value)
     }
 
+    /// The value of the position for `getting_move_for`, searched `depth`
+    /// plies deep. Mates are counted in plies from this position.
     fn minimax(
         &self,
         depth: i32,
-        mut alpha: f64,
-        mut beta: f64,
+        mut alpha: Score,
+        mut beta: Score,
         is_maximizing: bool,
         getting_move_for: Color,
         board_count: &mut u64,
-    ) -> f64 {
+    ) -> Score {
         *board_count += 1;
 
         if depth == 0 {
-            return self.value_for(getting_move_for);
+            let mut stats = SearchStats::default();
+            let value = if self.get_current_player_color() == getting_move_for {
+                self.quiescence(alpha, beta, 0, &mut stats)
+            } else {
+                -self.quiescence(-beta, -alpha, 0, &mut stats)
+            };
+            *board_count += stats.board_count;
+            return value;
         }
 
         let legal_moves = self.get_legal_moves();
+        if legal_moves.is_empty() {
+            return if !self.is_current_player_in_check() {
+                Score::DRAW
+            } else if self.get_current_player_color() == getting_move_for {
+                Score::mated_in(0)
+            } else {
+                Score::mate_in(0)
+            };
+        }
         let mut best_move_value;
 
         if is_maximizing {
-            best_move_value = -999999.0;
+            best_move_value = -Score::INFINITE;
 
             for m in &legal_moves {
-                let child_board_value = self.apply_eval_move(*m).minimax(
-                    depth - 1,
-                    alpha,
-                    beta,
-                    !is_maximizing,
-                    getting_move_for,
-                    board_count,
-                );
+                // the child counts mates from one ply later
+                let child_board_value = self
+                    .apply_eval_move(*m)
+                    .minimax(
+                        depth - 1,
+                        alpha.add_plies(-1),
+                        beta.add_plies(-1),
+                        !is_maximizing,
+                        getting_move_for,
+                        board_count,
+                    )
+                    .add_plies(1);
 
                 if child_board_value > best_move_value {
                     best_move_value = child_board_value;
@@ -239,17 +461,20 @@ pub trait Evaluate: Sized {
                 }
             }
         } else {
-            best_move_value = 999999.0;
+            best_move_value = Score::INFINITE;
 
             for m in &legal_moves {
-                let child_board_value = self.apply_eval_move(*m).minimax(
-                    depth - 1,
-                    alpha,
-                    beta,
-                    !is_maximizing,
-                    getting_move_for,
-                    board_count,
-                );
+                let child_board_value = self
+                    .apply_eval_move(*m)
+                    .minimax(
+                        depth - 1,
+                        alpha.add_plies(-1),
+                        beta.add_plies(-1),
+                        !is_maximizing,
+                        getting_move_for,
+                        board_count,
+                    )
+                    .add_plies(1);
                 if child_board_value < best_move_value {
                     best_move_value = child_board_value;
                 }
@@ -266,4 +491,103 @@ pub trait Evaluate: Sized {
 
         best_move_value
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uci_round_trip() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+        for fen in &positions {
+            let board = parse_fen(fen).unwrap();
+            for m in board.get_legal_moves() {
+                let repr = m.to_uci(&board);
+                assert_eq!(Move::from_uci(&board, &repr), Ok(m), "{} in {}", repr, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uci_castling() {
+        let board = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(Move::from_uci(&board, "e1g1"), Ok(Move::KingSideCastle));
+        assert_eq!(Move::from_uci(&board, "e1c1"), Ok(Move::QueenSideCastle));
+        assert_eq!(Move::KingSideCastle.to_uci(&board), "e1g1");
+        assert_eq!(Move::QueenSideCastle.to_uci(&board), "e1c1");
+
+        let board = board.change_turn();
+        assert_eq!(Move::from_uci(&board, "e8g8"), Ok(Move::KingSideCastle));
+        assert_eq!(Move::from_uci(&board, "e8c8"), Ok(Move::QueenSideCastle));
+        assert_eq!(Move::KingSideCastle.to_uci(&board), "e8g8");
+        assert_eq!(Move::QueenSideCastle.to_uci(&board), "e8c8");
+    }
+
+    #[test]
+    fn test_uci_promotion() {
+        let board = parse_fen("8/P7/8/8/8/8/7P/k6K w - - 0 1").unwrap();
+        let m = Move::from_uci(&board, "a7a8n").unwrap();
+        assert_eq!(m, Move::Promotion(A7, A8, Piece::Knight(WHITE, Position::new(-1, -1))));
+        assert_eq!(m.to_uci(&board), "a7a8n");
+
+        match board.play_move(m) {
+            GameResult::Continuing(next) => {
+                assert_eq!(next.get_piece(A8), Some(Piece::Knight(WHITE, A8)))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(Move::from_uci(&board, "a7a8x").is_err());
+        assert!(Move::from_uci(&board, "a7a9").is_err());
+        assert!(Move::from_uci(&board, "a7").is_err());
+        assert!(Move::from_uci(&board, "0000").is_err());
+    }
+
+    #[test]
+    fn test_quiescence_horizon() {
+        // the pawn on d5 is defended, so taking it loses the queen
+        let board = parse_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let (m, _, _) = board.get_best_next_move(0);
+        assert_ne!(m, Move::Piece(D1, D5));
+
+        let mut stats = SearchStats::default();
+        let after_capture = board.apply_eval_move(Move::Piece(D1, D5));
+        let value = after_capture.quiescence(-Score::INFINITE, Score::INFINITE, 1, &mut stats);
+        assert!(value > Score::from_value(after_capture.value_for(BLACK)));
+        assert!(stats.board_count > 0);
+        assert!(stats.seldepth > 1);
+    }
+
+    #[test]
+    fn test_quiescence_stand_pat() {
+        let board = Board::default();
+        assert!(board.get_noisy_moves().is_empty());
+        let mut stats = SearchStats::default();
+        assert_eq!(
+            board.quiescence(-Score::INFINITE, Score::INFINITE, 0, &mut stats),
+            Score::from_value(board.value_for(WHITE))
+        );
+        assert_eq!(stats.board_count, 0);
+    }
+
+    #[test]
+    fn test_noisy_moves() {
+        let board = parse_fen("3r3k/4P3/8/3pP3/8/8/8/K7 w - d6 0 1").unwrap();
+        let noisy = board.get_noisy_moves();
+        assert_eq!(noisy.len(), 9);
+        assert!(noisy.contains(&Move::Piece(E5, D6)));
+        assert!(noisy.iter().all(|m| matches!(m, Move::Promotion(E7, _, _)) || *m == Move::Piece(E5, D6)));
+
+        assert_eq!(board.get_move_gain(Move::Piece(E5, D6)), 10.0);
+        let queen = Piece::Queen(WHITE, Position::new(-1, -1));
+        assert_eq!(board.get_move_gain(Move::Promotion(E7, E8, queen)), 80.0);
+        assert_eq!(board.get_move_gain(Move::Promotion(E7, D8, queen)), 130.0);
+    }
+}
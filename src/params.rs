@@ -0,0 +1,496 @@
+use super::{Color, EvalTerm, EvalWeights, Piece};
+use alloc::string::String;
+use core::fmt;
+
+// Piece-square tables, in tenths of a pawn, from white's side of the
+// board: the first row is the eighth rank. Black uses them mirrored.
+//
+// There is one table for the middlegame and one for the endgame, since
+// where a piece belongs changes as the board empties. Most of all, the
+// king should shelter in the corner while there are pieces to attack it,
+// but walk to the center once there aren't.
+const MIDDLEGAME_KING_WEIGHTS: [[f64; 8]; 8] = [
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
+    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
+    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
+    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
+];
+
+const ENDGAME_KING_WEIGHTS: [[f64; 8]; 8] = [
+    [-5.0, -4.0, -3.0, -2.0, -2.0, -3.0, -4.0, -5.0],
+    [-3.0, -2.0, -1.0, 0.0, 0.0, -1.0, -2.0, -3.0],
+    [-3.0, -1.0, 2.0, 3.0, 3.0, 2.0, -1.0, -3.0],
+    [-3.0, -1.0, 3.0, 4.0, 4.0, 3.0, -1.0, -3.0],
+    [-3.0, -1.0, 3.0, 4.0, 4.0, 3.0, -1.0, -3.0],
+    [-3.0, -1.0, 2.0, 3.0, 3.0, 2.0, -1.0, -3.0],
+    [-3.0, -3.0, 0.0, 0.0, 0.0, 0.0, -3.0, -3.0],
+    [-5.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -5.0],
+];
+
+const MIDDLEGAME_QUEEN_WEIGHTS: [[f64; 8]; 8] = [
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
+];
+
+const ENDGAME_QUEEN_WEIGHTS: [[f64; 8]; 8] = [
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.5, 1.0, 1.0, 1.0, 1.0, 0.5, -1.0],
+    [-0.5, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -0.5],
+    [-0.5, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -0.5],
+    [-1.0, 0.5, 1.0, 1.0, 1.0, 1.0, 0.5, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+];
+
+const MIDDLEGAME_ROOK_WEIGHTS: [[f64; 8]; 8] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
+];
+
+const ENDGAME_ROOK_WEIGHTS: [[f64; 8]; 8] = [
+    [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5],
+    [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+const MIDDLEGAME_BISHOP_WEIGHTS: [[f64; 8]; 8] = [
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
+    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
+    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
+    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+];
+
+const ENDGAME_BISHOP_WEIGHTS: [[f64; 8]; 8] = [
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+];
+
+const MIDDLEGAME_KNIGHT_WEIGHTS: [[f64; 8]; 8] = [
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
+    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+];
+
+const ENDGAME_KNIGHT_WEIGHTS: [[f64; 8]; 8] = [
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+];
+
+const MIDDLEGAME_PAWN_WEIGHTS: [[f64; 8]; 8] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
+    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
+    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
+    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
+    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+const ENDGAME_PAWN_WEIGHTS: [[f64; 8]; 8] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [8.0, 8.0, 8.0, 8.0, 8.0, 8.0, 8.0, 8.0],
+    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
+    [3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0],
+    [1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5],
+    [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+
+/// The name of each kind of piece in a parameter file, in the order of
+/// `Piece::kind_index`.
+const KINDS: [&str; 6] = ["king", "queen", "rook", "bishop", "knight", "pawn"];
+
+/// The values of the evaluation that can be tuned without rebuilding the
+/// engine, in tenths of a pawn. Arrays by kind of piece are indexed from
+/// king to pawn, like `Piece::kind_index`.
+///
+/// Parameters can be read from and written to a simple text format with
+/// `parse` and `Display`:
+///
+/// ```text
+/// [material]
+/// queen = 90
+///
+/// [phase]
+/// queen = 4
+///
+/// [weights]
+/// mobility.knight = 0.4 0.4
+/// passed_pawn.6 = 4 6.5
+/// tempo = 1 0.5
+///
+/// [middlegame.knight]
+/// -5 -4 -3 -3 -3 -3 -4 -5
+/// ...
+/// ```
+///
+/// Terms with two values are for the middlegame and the endgame, and each
+/// table is eight rows of eight values, from the eighth rank down, as seen
+/// by white. Black uses them mirrored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvalParams {
+    /// The base value of each kind of piece. The king's is zero, since
+    /// each side always has one.
+    pub material: [f64; 6],
+    /// How much each kind of piece counts towards the game phase. The
+    /// pieces of the starting position should add up to `MAX_PHASE`.
+    pub phase: [i32; 6],
+    /// The piece-square tables used in the middlegame.
+    pub middlegame_tables: [[[f64; 8]; 8]; 6],
+    /// The piece-square tables used in the endgame.
+    pub endgame_tables: [[[f64; 8]; 8]; 6],
+    /// The weights of the pawn structure, mobility, king safety and tempo
+    /// terms.
+    pub weights: EvalWeights,
+}
+
+impl EvalParams {
+    pub const DEFAULT: Self = Self {
+        material: [0.0, 90.0, 50.0, 30.0, 30.0, 10.0],
+        phase: [0, 4, 2, 1, 1, 0],
+        middlegame_tables: [
+            MIDDLEGAME_KING_WEIGHTS,
+            MIDDLEGAME_QUEEN_WEIGHTS,
+            MIDDLEGAME_ROOK_WEIGHTS,
+            MIDDLEGAME_BISHOP_WEIGHTS,
+            MIDDLEGAME_KNIGHT_WEIGHTS,
+            MIDDLEGAME_PAWN_WEIGHTS,
+        ],
+        endgame_tables: [
+            ENDGAME_KING_WEIGHTS,
+            ENDGAME_QUEEN_WEIGHTS,
+            ENDGAME_ROOK_WEIGHTS,
+            ENDGAME_BISHOP_WEIGHTS,
+            ENDGAME_KNIGHT_WEIGHTS,
+            ENDGAME_PAWN_WEIGHTS,
+        ],
+        weights: EvalWeights::DEFAULT,
+    };
+
+    /// The base value of the piece.
+    #[inline]
+    pub fn get_material(&self, piece: &Piece) -> f64 { self.material[piece.kind_index()] }
+
+    /// How much the piece counts towards `Board::get_game_phase`.
+    #[inline]
+    pub fn get_phase_weight(&self, piece: &Piece) -> i32 { self.phase[piece.kind_index()] }
+
+    /// The piece-square table values of the piece on its square, without
+    /// its material.
+    #[inline]
+    pub fn get_square_values(&self, piece: &Piece) -> EvalTerm {
+        let pos = piece.get_pos();
+        let row = match piece.get_color() {
+            Color::White => 7 - pos.get_row(),
+            Color::Black => pos.get_row(),
+        } as usize;
+        let col = pos.get_col() as usize;
+        let kind = piece.kind_index();
+        EvalTerm::new(self.middlegame_tables[kind][row][col], self.endgame_tables[kind][row][col])
+    }
+
+    /// Read parameters in the format written by `Display`. Blank lines and
+    /// anything after a `#` are ignored, and every value the text leaves
+    /// out keeps its default.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut params = Self::DEFAULT;
+        let mut section = "";
+        let mut rows = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", i + 1, message);
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                if params.table_mut(section).is_some() && rows < 8 {
+                    return Err(error(format!("[{}] has {} rows instead of 8", section, rows)));
+                }
+                section = name.trim();
+                rows = 0;
+                if !["material", "phase", "weights"].contains(&section) && params.table_mut(section).is_none() {
+                    return Err(error(format!("unknown section [{}]", section)));
+                }
+                continue;
+            }
+
+            match section {
+                "material" | "phase" | "weights" => {
+                    let (key, values) = match line.split_once('=') {
+                        Some((key, values)) => (key.trim(), values),
+                        None => return Err(error(format!("expected `key = value`, found `{}`", line))),
+                    };
+                    let result = match section {
+                        "material" => kind(key).and_then(|kind| numbers(values).map(|[value]| params.material[kind] = value)),
+                        "phase" => kind(key).and_then(|kind| integer(values).map(|value| params.phase[kind] = value)),
+                        _ => set_weight(&mut params.weights, key, values),
+                    };
+                    result.map_err(error)?;
+                }
+                _ => match params.table_mut(section) {
+                    Some(_) if rows == 8 => return Err(error(format!("[{}] has more than 8 rows", section))),
+                    Some(table) => {
+                        table[rows] = numbers(line).map_err(error)?;
+                        rows += 1;
+                    }
+                    None => return Err(error(String::from("expected a [section] first"))),
+                },
+            }
+        }
+
+        if params.table_mut(section).is_some() && rows < 8 {
+            return Err(format!("[{}] has {} rows instead of 8", section, rows));
+        }
+        Ok(params)
+    }
+
+    /// The table named like `middlegame.knight`, if there is one.
+    fn table_mut(&mut self, name: &str) -> Option<&mut [[f64; 8]; 8]> {
+        let (phase, piece) = name.split_once('.')?;
+        let kind = KINDS.iter().position(|kind| *kind == piece)?;
+        match phase {
+            "middlegame" => Some(&mut self.middlegame_tables[kind]),
+            "endgame" => Some(&mut self.endgame_tables[kind]),
+            _ => None,
+        }
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self { Self::DEFAULT }
+}
+
+/// The index of the kind of piece with the given name.
+fn kind(name: &str) -> Result<usize, String> {
+    KINDS
+        .iter()
+        .position(|kind| *kind == name)
+        .ok_or_else(|| format!("unknown piece `{}`", name))
+}
+
+/// Exactly `N` numbers separated by whitespace.
+fn numbers<const N: usize>(text: &str) -> Result<[f64; N], String> {
+    let mut result = [0.0; N];
+    let mut count = 0;
+    for word in text.split_whitespace() {
+        let number = word.parse::<f64>().map_err(|_| format!("`{}` is not a number", word))?;
+        if let Some(slot) = result.get_mut(count) {
+            *slot = number;
+        }
+        count += 1;
+    }
+    if count != N {
+        return Err(format!("expected {} numbers, found {}", N, count));
+    }
+    Ok(result)
+}
+
+/// A single whole number.
+fn integer(text: &str) -> Result<i32, String> {
+    let [number] = numbers(text)?;
+    if number as i32 as f64 != number {
+        return Err(format!("`{}` is not a whole number", text.trim()));
+    }
+    Ok(number as i32)
+}
+
+/// A middlegame value and an endgame value.
+fn term(text: &str) -> Result<EvalTerm, String> { numbers(text).map(|[middlegame, endgame]| EvalTerm::new(middlegame, endgame)) }
+
+/// Set the weight named by `key`, like `tempo`, `mobility.knight` or
+/// `passed_pawn.6`, where 6 is the rank.
+fn set_weight(weights: &mut EvalWeights, key: &str, values: &str) -> Result<(), String> {
+    match key.split_once('.') {
+        Some(("mobility", piece)) => weights.mobility[kind(piece)?] = term(values)?,
+        Some(("king_attack", piece)) => weights.king_attack[kind(piece)?] = term(values)?,
+        Some(("passed_pawn", rank)) => match rank.parse::<usize>() {
+            Ok(rank @ 2..=7) => weights.passed_pawn[rank - 1] = term(values)?,
+            _ => return Err(format!("no pawn can stand on rank `{}`", rank)),
+        },
+        Some(_) => return Err(format!("unknown weight `{}`", key)),
+        None => match key {
+            "doubled_pawn" => weights.doubled_pawn = term(values)?,
+            "isolated_pawn" => weights.isolated_pawn = term(values)?,
+            "backward_pawn" => weights.backward_pawn = term(values)?,
+            "blocked_passed_pawn" => weights.blocked_passed_pawn = numbers(values).map(|[scale]| scale)?,
+            "pawn_shield" => weights.pawn_shield = term(values)?,
+            "semi_open_file" => weights.semi_open_file = term(values)?,
+            "open_file" => weights.open_file = term(values)?,
+            "king_attackers" => weights.king_attackers = numbers(values)?,
+            "tempo" => weights.tempo = term(values)?,
+            _ => return Err(format!("unknown weight `{}`", key)),
+        },
+    }
+    Ok(())
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let term = |term: EvalTerm| format!("{} {}", term.middlegame, term.endgame);
+        // kings and pawns have no mobility, and kings no material or attacks
+        let pieces = 1..5;
+
+        writeln!(f, "# Evaluation parameters, in tenths of a pawn.")?;
+        writeln!(f, "[material]")?;
+        for (name, material) in KINDS.iter().zip(self.material).skip(1) {
+            writeln!(f, "{} = {}", name, material)?;
+        }
+
+        writeln!(f, "\n[phase]")?;
+        for (name, phase) in KINDS.iter().zip(self.phase).skip(1) {
+            writeln!(f, "{} = {}", name, phase)?;
+        }
+
+        writeln!(f, "\n[weights]")?;
+        writeln!(f, "doubled_pawn = {}", term(self.weights.doubled_pawn))?;
+        writeln!(f, "isolated_pawn = {}", term(self.weights.isolated_pawn))?;
+        writeln!(f, "backward_pawn = {}", term(self.weights.backward_pawn))?;
+        for rank in 2..=7 {
+            writeln!(f, "passed_pawn.{} = {}", rank, term(self.weights.passed_pawn[rank - 1]))?;
+        }
+        writeln!(f, "blocked_passed_pawn = {}", self.weights.blocked_passed_pawn)?;
+        for kind in pieces.clone() {
+            writeln!(f, "mobility.{} = {}", KINDS[kind], term(self.weights.mobility[kind]))?;
+        }
+        writeln!(f, "pawn_shield = {}", term(self.weights.pawn_shield))?;
+        writeln!(f, "semi_open_file = {}", term(self.weights.semi_open_file))?;
+        writeln!(f, "open_file = {}", term(self.weights.open_file))?;
+        for kind in pieces {
+            writeln!(f, "king_attack.{} = {}", KINDS[kind], term(self.weights.king_attack[kind]))?;
+        }
+        write!(f, "king_attackers =")?;
+        for scale in self.weights.king_attackers {
+            write!(f, " {}", scale)?;
+        }
+        writeln!(f, "\ntempo = {}", term(self.weights.tempo))?;
+
+        for (phase, tables) in [("middlegame", &self.middlegame_tables), ("endgame", &self.endgame_tables)] {
+            for (kind, table) in tables.iter().enumerate() {
+                writeln!(f, "\n[{}.{}]", phase, KINDS[kind])?;
+                for row in table {
+                    for (col, value) in row.iter().enumerate() {
+                        write!(f, "{}{:>5}", if col == 0 { "" } else { " " }, value)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_parse() {
+        let params = EvalParams::DEFAULT;
+        assert_eq!(EvalParams::parse(&params.to_string()), Ok(params));
+        assert_eq!(EvalParams::parse(""), Ok(params));
+
+        let text = "
+            # only what changes
+            [material]
+            knight = 32.5  # a little more than a bishop
+
+            [phase]
+            rook = 3
+
+            [weights]
+            mobility.rook = 0.3 0.5
+            king_attackers = 0 0 1 1 1 1 1 1
+            doubled_pawn = -1.5 -2.5
+            passed_pawn.7 = 7 12
+            blocked_passed_pawn = 0.25
+
+            [endgame.pawn]
+            0 0 0 0 0 0 0 0
+            9 9 9 9 9 9 9 9
+            5 5 5 5 5 5 5 5
+            3 3 3 3 3 3 3 3
+            1 1 1 1 1 1 1 1
+            0 0 0 0 0 0 0 0
+            0 0 0 0 0 0 0 0
+            0 0 0 0 0 0 0 0
+        ";
+        let parsed = EvalParams::parse(text).unwrap();
+        assert_eq!(parsed.material[4], 32.5);
+        assert_eq!(parsed.weights.mobility[2], EvalTerm::new(0.3, 0.5));
+        assert_eq!(parsed.weights.king_attackers, [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(parsed.phase, [0, 4, 3, 1, 1, 0]);
+        assert_eq!(parsed.weights.doubled_pawn, EvalTerm::new(-1.5, -2.5));
+        assert_eq!(parsed.weights.passed_pawn[6], EvalTerm::new(7.0, 12.0));
+        assert_eq!(parsed.weights.blocked_passed_pawn, 0.25);
+        assert_eq!(parsed.endgame_tables[5][1], [9.0; 8]);
+        assert_eq!(parsed.middlegame_tables, params.middlegame_tables);
+        assert_eq!(parsed.weights.tempo, params.weights.tempo);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| EvalParams::parse(text).unwrap_err();
+        assert_eq!(error("knight = 30"), "line 1: expected a [section] first");
+        assert_eq!(error("[material]\nknight = thirty"), "line 2: `thirty` is not a number");
+        assert_eq!(error("[material]\ndragon = 30"), "line 2: unknown piece `dragon`");
+        assert_eq!(error("[material]\nknight 30"), "line 2: expected `key = value`, found `knight 30`");
+        assert_eq!(error("[weights]\ntempo = 1"), "line 2: expected 2 numbers, found 1");
+        assert_eq!(error("[weights]\nmobility.dragon = 1 1"), "line 2: unknown piece `dragon`");
+        assert_eq!(error("[weights]\nspeed = 1 1"), "line 2: unknown weight `speed`");
+        assert_eq!(error("[weights]\npassed_pawn.8 = 1 1"), "line 2: no pawn can stand on rank `8`");
+        assert_eq!(error("[phase]\nrook = 2.5"), "line 2: `2.5` is not a whole number");
+        assert_eq!(error("[openings]"), "line 1: unknown section [openings]");
+        assert_eq!(error("[endgame.pawn]\n1 2 3 4 5 6 7 8"), "[endgame.pawn] has 1 rows instead of 8");
+        assert_eq!(error("[endgame.pawn]\n1 2 3\n[material]"), "line 2: expected 8 numbers, found 3");
+        let rows = "0 0 0 0 0 0 0 0\n".repeat(9);
+        assert_eq!(error(&format!("[endgame.pawn]\n{}", rows)), "line 10: [endgame.pawn] has more than 8 rows");
+    }
+}
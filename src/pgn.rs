@@ -0,0 +1,536 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use core::str::Chars;
+
+use crate::board::Board;
+use crate::game::{Game, GameAction, GameError, GameOver};
+use crate::util::{format_fen, parse_fen, parse_san_move};
+use crate::Color;
+
+/// The tags every PGN game carries, in the order they are exported,
+/// along with the value used when a tag is unknown.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Exported movetext is wrapped to stay within this many characters.
+const MAX_LINE_LENGTH: usize = 80;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnError {
+    /// The ply (counting from 1) at which the error occurred, or the number
+    /// of plies read so far for errors outside of a move.
+    pub ply: usize,
+    pub reason: String,
+}
+
+impl core::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        write!(f, "ply {}: {}", self.ply, self.reason)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    /// Numeric annotation glyphs, e.g. `1` for `$1` (a good move).
+    pub nags: Vec<u32>,
+    pub comment: Option<String>,
+}
+
+impl From<&str> for PgnMove {
+    fn from(san: &str) -> Self {
+        Self {
+            san: san.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    /// A comment placed before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl Pgn {
+    /// Record a game along with the seven tag roster, using `?` for any
+    /// tag that isn't known yet.
+    pub fn from_game(game: &Game) -> Self {
+        let mut pgn = Self {
+            moves: game.history().iter().map(|ply| PgnMove::from(ply.san.as_str())).collect(),
+            result: result_token(&game.status).to_string(),
+            ..Self::default()
+        };
+        for (name, value) in &SEVEN_TAG_ROSTER {
+            pgn.set_tag(name, value);
+        }
+        pgn.set_tag("Result", &pgn.result.clone());
+
+        let starting_board = game.get_starting_board();
+        if starting_board != Board::default() {
+            if let Ok(fen) = format_fen(&starting_board) {
+                pgn.set_tag("SetUp", "1");
+                pgn.set_tag("FEN", &fen);
+            }
+        }
+        pgn
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Add a tag, replacing the value of an existing tag with the same name.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Replay the moves into a game. A decisive or drawn result that wasn't
+    /// reached on the board is recorded as a resignation or an agreed draw.
+    pub fn to_game(&self) -> Result<Game, PgnError> {
+        let mut game = match self.get_tag("FEN") {
+            Some(fen) => Game::from_fen(fen, None, None).map_err(|_| PgnError {
+                ply: 0,
+                reason: format!("invalid FEN `{}`", fen),
+            })?,
+            None => Game::default(),
+        };
+
+        for (i, m) in self.moves.iter().enumerate() {
+            let error = |reason: String| PgnError { ply: i + 1, reason };
+            // `--` is the null move some programs write for a pass
+            let action = if m.san == "--" {
+                GameAction::Pass
+            } else if let Err(e) = parse_san_move(&game.board, &m.san) {
+                return Err(error(format!("invalid move `{}`: {}", m.san, e)));
+            } else {
+                GameAction::from(m.san.as_str())
+            };
+            match game.make_move(&action) {
+                Ok(_) => {}
+                Err(GameError::GameAlreadyOver) => {
+                    return Err(error(format!("move `{}` after the game ended", m.san)))
+                }
+                Err(_) => return Err(error(format!("illegal move `{}`", m.san))),
+            }
+        }
+
+        if game.status.is_none() {
+            game.status = match self.result.as_str() {
+                "1-0" => Some(GameOver::BlackResigns),
+                "0-1" => Some(GameOver::WhiteResigns),
+                "1/2-1/2" => Some(GameOver::DrawAccepted),
+                _ => None,
+            };
+        }
+        Ok(game)
+    }
+
+    /// The number of the first move and whether black plays it.
+    fn first_move(&self) -> (u32, bool) {
+        match self.get_tag("FEN").map(parse_fen) {
+            Some(Ok(board)) => (
+                board.get_fullmove_number(),
+                board.get_turn_color() == Color::Black,
+            ),
+            _ => (1, false),
+        }
+    }
+}
+
+impl core::fmt::Display for Pgn {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        for (name, default) in &SEVEN_TAG_ROSTER {
+            let value = match *name {
+                "Result" => self.result.as_str(),
+                name => self.get_tag(name).unwrap_or(default),
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+        }
+        for (name, value) in &self.tags {
+            if SEVEN_TAG_ROSTER.iter().all(|(roster, _)| roster != name) {
+                writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("{{{}}}", comment.replace('}', "")));
+        }
+        let (mut number, mut black) = self.first_move();
+        // black's move only needs a number after a comment or at the start
+        let mut needs_number = true;
+        for m in &self.moves {
+            if !black {
+                tokens.push(format!("{}.", number));
+            } else if needs_number {
+                tokens.push(format!("{}...", number));
+            }
+            tokens.push(m.san.clone());
+            tokens.extend(m.nags.iter().map(|nag| format!("${}", nag)));
+            needs_number = false;
+            if let Some(comment) = &m.comment {
+                tokens.push(format!("{{{}}}", comment.replace('}', "")));
+                needs_number = true;
+            }
+            if black {
+                number += 1;
+            }
+            black = !black;
+        }
+        tokens.push(self.result.clone());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            } else if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+fn result_token(status: &Option<GameOver>) -> &'static str {
+    match status {
+        Some(GameOver::WhiteCheckmates) | Some(GameOver::BlackResigns) => "1-0",
+        Some(GameOver::BlackCheckmates) | Some(GameOver::WhiteResigns) => "0-1",
+        Some(GameOver::Stalemate)
+        | Some(GameOver::DrawAccepted)
+        | Some(GameOver::ThreefoldRepetition)
+        | Some(GameOver::FivefoldRepetition)
+        | Some(GameOver::FiftyMoveRule)
+        | Some(GameOver::SeventyFiveMoveRule) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Translate traditional move suffix annotations into their NAG.
+fn suffix_nag(suffix: &str) -> Option<u32> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// Parse every game in a PGN file. Variations are skipped, and comments
+/// are attached to the move they follow.
+pub fn parse_pgn(pgn: &str) -> Result<Vec<Pgn>, PgnError> {
+    let mut games = vec![];
+    let mut game = Pgn::default();
+    let mut chars = pgn.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let ply = game.moves.len();
+        let error = |reason: &str| PgnError {
+            ply,
+            reason: reason.to_string(),
+        };
+        match c {
+            '[' => {
+                // a tag after movetext starts a game with no result token
+                if !game.moves.is_empty() {
+                    finish_game(&mut games, &mut game);
+                }
+                let (name, value) = parse_tag(&mut chars).ok_or_else(|| error("invalid tag"))?;
+                game.tags.push((name, value));
+            }
+            '{' => {
+                let comment = take_until(&mut chars, '}').ok_or_else(|| error("unterminated comment"))?;
+                add_comment(&mut game, comment.trim());
+            }
+            ';' => {
+                let comment = take_until(&mut chars, '\n').unwrap_or_default();
+                add_comment(&mut game, comment.trim());
+            }
+            '(' => skip_variation(&mut chars).ok_or_else(|| error("unterminated variation"))?,
+            ')' => return Err(error("unexpected end of variation")),
+            '$' => {
+                let nag = take_token(&mut chars, String::new());
+                let nag = nag.parse().map_err(|_| error("invalid NAG"))?;
+                match game.moves.last_mut() {
+                    Some(m) => m.nags.push(nag),
+                    None => return Err(error("NAG before the first move")),
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let token = take_token(&mut chars, c.to_string());
+                if RESULTS.contains(&token.as_str()) {
+                    game.result = token;
+                    finish_game(&mut games, &mut game);
+                    continue;
+                }
+
+                // drop move numbers such as `12.` or `12...` from `12...Nf6`
+                let token = match token.find('.') {
+                    Some(i) if token[..i].chars().all(|c| c.is_ascii_digit()) => {
+                        token[i..].trim_start_matches('.')
+                    }
+                    _ => token.as_str(),
+                };
+                if token.is_empty() {
+                    continue;
+                }
+
+                let san = token.trim_end_matches(['!', '?']);
+                let mut m = PgnMove::from(san);
+                m.nags.extend(suffix_nag(&token[san.len()..]));
+                game.moves.push(m);
+            }
+        }
+    }
+
+    if !game.tags.is_empty() || !game.moves.is_empty() {
+        finish_game(&mut games, &mut game);
+    }
+    Ok(games)
+}
+
+fn finish_game(games: &mut Vec<Pgn>, game: &mut Pgn) {
+    let mut finished = core::mem::take(game);
+    if finished.result.is_empty() {
+        finished.result = finished.get_tag("Result").unwrap_or("*").to_string();
+    }
+    games.push(finished);
+}
+
+fn add_comment(game: &mut Pgn, comment: &str) {
+    let slot = match game.moves.last_mut() {
+        Some(m) => &mut m.comment,
+        None => &mut game.comment,
+    };
+    *slot = Some(match slot.take() {
+        Some(previous) => format!("{} {}", previous, comment),
+        None => comment.to_string(),
+    });
+}
+
+/// Read everything up to (and consuming) `end`, or `None` if the input
+/// runs out first.
+fn take_until(chars: &mut Peekable<Chars>, end: char) -> Option<String> {
+    let mut result = String::new();
+    for c in chars.by_ref() {
+        if c == end {
+            return Some(result);
+        }
+        result.push(c);
+    }
+    None
+}
+
+/// Read the rest of a movetext token that started with `token`.
+fn take_token(chars: &mut Peekable<Chars>, mut token: String) -> String {
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || "[]{}();$".contains(c) {
+            break;
+        }
+        token.push(c);
+        chars.next();
+    }
+    token
+}
+
+fn parse_tag(chars: &mut Peekable<Chars>) -> Option<(String, String)> {
+    let contents = take_until(chars, ']')?;
+    let contents = contents.trim();
+    let quote = contents.find('"')?;
+    let name = contents[..quote].trim();
+    let quoted = contents[quote + 1..].strip_suffix('"')?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut value = String::new();
+    let mut escaped = false;
+    for c in quoted.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c => {
+                value.push(c);
+                escaped = false;
+            }
+        }
+    }
+    Some((name.to_string(), value))
+}
+
+/// Skip a (possibly nested) variation whose opening parenthesis has
+/// already been read, along with any comments inside it.
+fn skip_variation(chars: &mut Peekable<Chars>) -> Option<()> {
+    let mut depth = 1;
+    while depth > 0 {
+        match chars.next()? {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '{' => {
+                take_until(chars, '}')?;
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Piece;
+    use crate::position::Position;
+
+    #[test]
+    fn test_export() {
+        let mut game = Game::default();
+        for m in &["f3", "e5", "g4", "Qh4"] {
+            game.make_move(&GameAction::from(*m)).expect(m);
+        }
+        let mut pgn = Pgn::from_game(&game);
+        pgn.set_tag("White", "Fool");
+        pgn.set_tag("Annotator", "Nobody");
+        pgn.moves[1].comment = Some(String::from("opening the diagonal"));
+        pgn.moves[2].nags.push(4);
+
+        assert_eq!(
+            pgn.to_string(),
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Fool\"]\n\
+             [Black \"?\"]\n\
+             [Result \"0-1\"]\n\
+             [Annotator \"Nobody\"]\n\
+             \n\
+             1. f3 e5 {opening the diagonal} 2. g4 $4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn test_export_from_position() {
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1", None, None).unwrap();
+        for m in &["Kd7", "e4", "Ke6"] {
+            game.make_move(&GameAction::from(*m)).expect(m);
+        }
+        let pgn = Pgn::from_game(&game);
+        assert_eq!(pgn.get_tag("SetUp"), Some("1"));
+        assert!(pgn.to_string().ends_with("1... Kd7 2. e4 Ke6 *\n"));
+
+        let replayed = parse_pgn(&pgn.to_string()).unwrap()[0].to_game().unwrap();
+        assert_eq!(replayed.board, game.board);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+[Event "Casual \"blitz\""]
+[Site "?"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3 $1 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 {develops}
+3. Bb5!? a6 ; end of line comment
+4. Ba4 1-0
+
+[Event "Second"]
+
+1.d4 d5 2.c4 dxc4 3.e3 Nf6 4.Bxc4 1/2-1/2
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let first = &games[0];
+        assert_eq!(first.get_tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(first.comment, Some(String::from("Opening comment")));
+        assert_eq!(
+            first.moves.iter().map(|m| m.san.as_str()).collect::<Vec<&str>>(),
+            vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]
+        );
+        assert_eq!(first.moves[2].nags, vec![1]);
+        assert_eq!(first.moves[3].comment, Some(String::from("develops")));
+        assert_eq!(first.moves[4].nags, vec![5]);
+        assert_eq!(first.moves[5].comment, Some(String::from("end of line comment")));
+        assert_eq!(first.result, "1-0");
+
+        let game = first.to_game().unwrap();
+        assert_eq!(game.status, Some(GameOver::BlackResigns));
+        assert_eq!(
+            game.board.get_piece(Position::pgn("a4").unwrap()),
+            Some(Piece::Bishop(Color::White, Position::pgn("a4").unwrap()))
+        );
+
+        let second = games[1].to_game().unwrap();
+        assert_eq!(second.history().len(), 7);
+        assert_eq!(second.status, Some(GameOver::DrawAccepted));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "1. e4 d5 2. exd5 Nf6 3. Bb5+ c6 4. dxc6 Qb6 5. cxb7+ Qxb5 6. bxc8=R+ *";
+        let pgn = &parse_pgn(text).unwrap()[0];
+        let game = pgn.to_game().unwrap();
+        let exported = Pgn::from_game(&game);
+        assert_eq!(exported.moves, pgn.moves);
+        assert_eq!(parse_pgn(&exported.to_string()).unwrap()[0].moves, pgn.moves);
+    }
+
+    #[test]
+    fn test_pass() {
+        let mut game = Game::default();
+        game.make_move(&GameAction::from("e4")).expect("e4");
+        game.make_move(&GameAction::Pass).expect("pass");
+        game.make_move(&GameAction::from("d4")).expect("d4");
+        let pgn = Pgn::from_game(&game);
+        assert_eq!(pgn.to_string().lines().last(), Some("1. e4 -- 2. d4 *"));
+
+        let replayed = parse_pgn(&pgn.to_string()).unwrap()[0].to_game().unwrap();
+        assert_eq!(replayed.board, game.board);
+        assert_eq!(replayed.history()[1].chess_move, None);
+    }
+
+    #[test]
+    fn test_invalid_move() {
+        let pgn = &parse_pgn("1. e4 e5 2. Nf3 Nf6 3. Ke3 *").unwrap()[0];
+        let error = pgn.to_game().err().expect("Ke3 is illegal");
+        assert_eq!(error.ply, 5);
+        assert!(error.reason.contains("Ke3"));
+
+        let pgn = &parse_pgn("1. f3 e5 2. g4 Qh4 3. a3 0-1").unwrap()[0];
+        assert_eq!(pgn.to_game().err().expect("game is over").ply, 5);
+
+        assert_eq!(parse_pgn("1. e4 {never closed").unwrap_err().ply, 1);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{Board, Color, Move, Position};
+use super::{Board, Color, EvalParams, Move, Position};
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::convert::TryFrom;
@@ -13,137 +13,6 @@ pub enum Piece {
     Pawn(Color, Position),
 }
 
-const WHITE_KING_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
-    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
-    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
-    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
-];
-
-const BLACK_KING_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
-    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
-    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
-    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-];
-
-const WHITE_QUEEN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
-];
-const BLACK_QUEEN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
-    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
-];
-
-const WHITE_ROOK_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
-];
-
-const BLACK_ROOK_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
-
-const WHITE_BISHOP_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
-    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
-    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
-    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-];
-
-const BLACK_BISHOP_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
-    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
-    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
-    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-];
-
-const WHITE_KNIGHT_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
-    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
-    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
-    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
-    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
-    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-];
-
-const BLACK_KNIGHT_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
-    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
-    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
-    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
-    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
-    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-];
-
-const WHITE_PAWN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
-    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
-    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
-    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
-    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
-    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
-
-const BLACK_PAWN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
-    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
-    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
-    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
-    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
-    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
-
 impl core::fmt::Display for Piece {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
         write!(
@@ -214,38 +83,20 @@ impl Piece {
         }
     }
 
+    /// The value of the piece on its square, in tenths of a pawn, in the
+    /// middlegame and in the endgame, with the default `EvalParams`.
+    /// `Board::value_for` blends the two by the game phase.
     #[inline]
-    pub fn get_weighted_value(&self) -> f64 {
-        let weights = match self {
-            Self::King(c, _) => match c {
-                Color::White => WHITE_KING_POSITION_WEIGHTS,
-                Color::Black => BLACK_KING_POSITION_WEIGHTS,
-            },
-            Self::Queen(c, _) => match c {
-                Color::White => WHITE_QUEEN_POSITION_WEIGHTS,
-                Color::Black => BLACK_QUEEN_POSITION_WEIGHTS,
-            },
-            Self::Rook(c, _) => match c {
-                Color::White => WHITE_ROOK_POSITION_WEIGHTS,
-                Color::Black => BLACK_ROOK_POSITION_WEIGHTS,
-            },
-            Self::Bishop(c, _) => match c {
-                Color::White => WHITE_BISHOP_POSITION_WEIGHTS,
-                Color::Black => BLACK_BISHOP_POSITION_WEIGHTS,
-            },
-            Self::Knight(c, _) => match c {
-                Color::White => WHITE_KNIGHT_POSITION_WEIGHTS,
-                Color::Black => BLACK_KNIGHT_POSITION_WEIGHTS,
-            },
-            Self::Pawn(c, _) => match c {
-                Color::White => WHITE_PAWN_POSITION_WEIGHTS,
-                Color::Black => BLACK_PAWN_POSITION_WEIGHTS,
-            },
-        };
-        weights[(7 - self.get_pos().get_row()) as usize][self.get_pos().get_col() as usize]
-            + (self.get_material_value() * 10) as f64
+    pub fn get_weighted_values(&self) -> (f64, f64) {
+        let square = EvalParams::DEFAULT.get_square_values(self);
+        let material = EvalParams::DEFAULT.get_material(self);
+        (square.middlegame + material, square.endgame + material)
     }
 
+    /// The value of the piece on its square in the middlegame.
+    #[inline]
+    pub fn get_weighted_value(&self) -> f64 { self.get_weighted_values().0 }
+
     #[inline]
     pub fn with_color(&self, color: Color) -> Self {
         match *self {
@@ -282,6 +133,19 @@ impl Piece {
         }
     }
 
+    /// A distinct index for each kind of piece, from king to pawn.
+    #[inline]
+    pub(crate) fn kind_index(&self) -> usize {
+        match self {
+            Self::King(_, _) => 0,
+            Self::Queen(_, _) => 1,
+            Self::Rook(_, _) => 2,
+            Self::Bishop(_, _) => 3,
+            Self::Knight(_, _) => 4,
+            Self::Pawn(_, _) => 5,
+        }
+    }
+
     #[inline]
     pub fn is_king(&self) -> bool { matches!(self, Self::King(_, _)) }
 
@@ -339,6 +203,8 @@ impl Piece {
         }
     }
 
+    /// The legal moves of this piece, found by checking each candidate
+    /// square in turn. `Board::get_legal_moves` uses bitboards instead.
     #[inline]
     pub(crate) fn get_legal_moves(&self, board: &Board) -> Vec<Move> {
         let mut result = Vec::new();
@@ -374,6 +240,23 @@ impl Piece {
                 if up_right.is_on_board() && board.has_enemy_piece(up_right, ally_color) {
                     result.push(Move::Piece(pos, up_right))
                 }
+
+                // moves onto the last row promote to any of four pieces
+                if up.get_row() == 0 || up.get_row() == 7 {
+                    let offboard = Position::new(-1, -1);
+                    for m in core::mem::take(&mut result) {
+                        if let Move::Piece(from, to) = m {
+                            for promotion in [
+                                Piece::Queen(ally_color, offboard),
+                                Piece::Rook(ally_color, offboard),
+                                Piece::Bishop(ally_color, offboard),
+                                Piece::Knight(ally_color, offboard),
+                            ] {
+                                result.push(Move::Promotion(from, to, promotion));
+                            }
+                        }
+                    }
+                }
             }
 
             Self::King(ally_color, pos) => {
@@ -393,7 +276,8 @@ impl Piece {
                 }
                 if board.can_kingside_castle(ally_color) {
                     result.push(Move::KingSideCastle);
-                } else if board.can_queenside_castle(ally_color) {
+                }
+                if board.can_queenside_castle(ally_color) {
                     result.push(Move::QueenSideCastle);
                 }
             }
@@ -490,12 +374,12 @@ impl Piece {
             .filter(|x| match x {
                 Move::Piece(from, to) => {
                     if from.is_on_board() && to.is_on_board() {
-                        board.is_legal_move(*x, color)
+                        board.is_legal_move_mailbox(*x, color)
                     } else {
                         false
                     }
                 }
-                _ => board.is_legal_move(*x, color),
+                _ => board.is_legal_move_mailbox(*x, color),
             })
             .collect::<Vec<Move>>()
     }
@@ -145,7 +145,7 @@ impl Position {
             _ => return Err(format!("invalid column character `{}`", col)),
         };
 
-        if 1 <= row || row <= 8 {
+        if (1..=8).contains(&row) {
             Ok(Self::new(row - 1, c))
         } else {
             Err(format!("invalid row number `{}`", row))
@@ -257,19 +257,8 @@ impl Position {
             return Vec::new();
         }
 
-        let row_step;
-        let col_step;
-        if self.is_left_of(to) {
-            col_step = 1;
-        } else {
-            col_step = -1;
-        }
-
-        if self.is_below(to) {
-            row_step = 1;
-        } else {
-            row_step = -1;
-        }
+        let col_step = if self.is_left_of(to) { 1 } else { -1 };
+        let row_step = if self.is_below(to) { 1 } else { -1 };
 
         let mut acc = *self;
         let mut result = Vec::new();
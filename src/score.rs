@@ -0,0 +1,148 @@
+use core::ops::{Add, Neg, Sub};
+
+/// The furthest away a mate can be and still be told apart from an
+/// ordinary score, in plies.
+const MAX_MATE_PLY: i32 = 1000;
+
+/// The value of a position for the player to move, in centipawns, or the
+/// distance to a forced mate.
+///
+/// Mates are scored beyond any evaluation: mating in `n` plies is worth
+/// `MATE - n` and being mated in `n` plies is worth `-(MATE - n)`, so
+/// scores compare the way they should and faster mates are preferred.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Score(i32);
+
+impl Score {
+    /// A drawn position, such as stalemate.
+    pub const DRAW: Self = Self(0);
+
+    /// Above every score, used to open the search window.
+    pub const INFINITE: Self = Self(1_000_000);
+
+    /// The score of a mate on the board.
+    const MATE: i32 = 100_000;
+
+    /// The largest score an evaluation can have.
+    const MAX_EVAL: i32 = Self::MATE - MAX_MATE_PLY - 1;
+
+    /// A score in centipawns, clamped below the mate scores.
+    #[inline]
+    pub fn centipawns(cp: i32) -> Self { Self(cp.clamp(-Self::MAX_EVAL, Self::MAX_EVAL)) }
+
+    /// Convert a value from `Evaluate::value_for`, where a pawn is worth
+    /// 10, to centipawns.
+    #[inline]
+    pub fn from_value(value: f64) -> Self {
+        // round to the nearest centipawn, since `f64::round` needs std
+        let cp = value * 10.0;
+        Self::centipawns(if cp < 0.0 { cp - 0.5 } else { cp + 0.5 } as i32)
+    }
+
+    /// The player to move mates in the given number of plies.
+    #[inline]
+    pub fn mate_in(plies: i32) -> Self { Self(Self::MATE - plies) }
+
+    /// The player to move is mated in the given number of plies.
+    #[inline]
+    pub fn mated_in(plies: i32) -> Self { Self(-Self::MATE + plies) }
+
+    /// Is this the score of a forced mate, for either player?
+    #[inline]
+    pub fn is_mate(&self) -> bool { self.0.abs() > Self::MAX_EVAL && self.0.abs() <= Self::MATE }
+
+    /// The score in centipawns. Mates are given their raw encoding.
+    #[inline]
+    pub fn get_centipawns(&self) -> i32 { self.0 }
+
+    /// The number of moves until mate, negative when the player to move is
+    /// the one mated, or `None` if the score isn't a mate.
+    pub fn get_mate(&self) -> Option<i32> {
+        if !self.is_mate() {
+            return None;
+        }
+        let moves = (Self::MATE - self.0.abs() + 1) / 2;
+        Some(if self.0 > 0 { moves } else { -moves })
+    }
+
+    /// Move a mate the given number of plies further away, leaving every
+    /// other score as it is. Mates are stored relative to the position
+    /// they were found in, but reported relative to the root.
+    #[inline]
+    pub(crate) fn add_plies(self, plies: i32) -> Self {
+        if !self.is_mate() {
+            self
+        } else if self.0 > 0 {
+            Self(self.0 - plies)
+        } else {
+            Self(self.0 + plies)
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self { Self(-self.0) }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self { Self(self.0.saturating_add(other.0)) }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self { Self(self.0.saturating_sub(other.0)) }
+}
+
+impl core::fmt::Display for Score {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        match self.get_mate() {
+            Some(moves) => write!(f, "#{}", moves),
+            None => write!(f, "{:+.2}", self.0 as f64 / 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_mate_scores() {
+        assert!(Score::mate_in(1) > Score::mate_in(3));
+        assert!(Score::mate_in(3) > Score::centipawns(5000));
+        assert!(Score::mated_in(2) < Score::mated_in(4));
+        assert!(Score::mated_in(4) < Score::centipawns(-5000));
+        assert_eq!(-Score::mate_in(3), Score::mated_in(3));
+        assert!(Score::INFINITE > Score::mate_in(0));
+
+        assert_eq!(Score::mate_in(1).get_mate(), Some(1));
+        assert_eq!(Score::mate_in(3).get_mate(), Some(2));
+        assert_eq!(Score::mated_in(2).get_mate(), Some(-1));
+        assert_eq!(Score::centipawns(250).get_mate(), None);
+        assert!(!Score::INFINITE.is_mate());
+
+        assert_eq!(Score::mate_in(3).add_plies(2), Score::mate_in(5));
+        assert_eq!(Score::mated_in(3).add_plies(-2), Score::mated_in(1));
+        assert_eq!(Score::DRAW.add_plies(2), Score::DRAW);
+    }
+
+    #[test]
+    fn test_evaluations() {
+        assert_eq!(Score::from_value(10.0), Score::centipawns(100));
+        assert_eq!(Score::from_value(-0.54), Score::centipawns(-5));
+        // an evaluation never looks like a mate
+        assert!(!Score::from_value(1e9).is_mate());
+        assert!(!Score::from_value(f64::INFINITY).is_mate());
+        assert_eq!(Score::centipawns(150).to_string(), "+1.50");
+        assert_eq!(Score::mated_in(4).to_string(), "#-2");
+    }
+}
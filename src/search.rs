@@ -0,0 +1,1301 @@
+use super::{Color, Evaluate, Move, Piece, Score, BLACK, WHITE};
+use alloc::vec::Vec;
+use core::{cmp::Reverse, mem::size_of};
+
+/// The size of the table made by `TranspositionTable::default`, in
+/// megabytes.
+pub const DEFAULT_TABLE_SIZE_MB: usize = 16;
+
+/// How a stored value relates to the true value of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bound {
+    /// The value is exact.
+    Exact,
+    /// The search failed high, so the true value is at least this.
+    Lower,
+    /// The search failed low, so the true value is at most this.
+    Upper,
+}
+
+/// The result of searching a single position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableEntry {
+    pub hash: u64,
+    pub depth: i32,
+    /// The value for the player to move in the position, with mates
+    /// counted from the position itself.
+    pub value: Score,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+/// A fixed-size table of searched positions, indexed by their hash.
+/// When two positions share a slot, the one searched deeper is kept.
+#[derive(Clone, Debug)]
+pub struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self { Self::new(DEFAULT_TABLE_SIZE_MB) }
+}
+
+impl TranspositionTable {
+    /// Create a table taking up roughly `size_mb` megabytes.
+    pub fn new(size_mb: usize) -> Self { Self::with_capacity(size_mb * 1024 * 1024 / size_of::<Option<TableEntry>>()) }
+
+    /// Create a table holding `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: vec![None; capacity.max(1)],
+        }
+    }
+
+    /// A table sized for a single search `depth` plies deep, so a shallow
+    /// search doesn't have to clear a large table first. It is never
+    /// bigger than the default.
+    pub(crate) fn for_depth(depth: i32) -> Self {
+        let default = DEFAULT_TABLE_SIZE_MB * 1024 * 1024 / size_of::<Option<TableEntry>>();
+        Self::with_capacity((1 << (8 + 2 * depth.clamp(0, 10))).min(default))
+    }
+
+    /// The number of entries the table can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize { self.entries.len() }
+
+    /// Forget every stored position.
+    pub fn clear(&mut self) {
+        for entry in &mut self.entries {
+            *entry = None;
+        }
+    }
+
+    #[inline]
+    fn index(&self, hash: u64) -> usize { (hash % self.entries.len() as u64) as usize }
+
+    /// Look up the entry for a position, if it is still stored.
+    #[inline]
+    pub fn get(&self, hash: u64) -> Option<&TableEntry> {
+        self.entries[self.index(hash)]
+            .as_ref()
+            .filter(|entry| entry.hash == hash)
+    }
+
+    /// Store an entry, unless its slot holds one searched deeper.
+    pub fn insert(&mut self, entry: TableEntry) {
+        let index = self.index(entry.hash);
+        let slot = &mut self.entries[index];
+        match slot {
+            Some(old) if old.depth > entry.depth => {}
+            _ => *slot = Some(entry),
+        }
+    }
+
+    /// How full the table is, in permille, estimated from the first
+    /// thousand slots.
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        sample.iter().filter(|entry| entry.is_some()).count() * 1000 / sample.len()
+    }
+}
+
+/// Counters collected over a search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of positions visited.
+    pub board_count: u64,
+    /// Positions found in the transposition table.
+    pub tt_hits: u64,
+    /// Positions not found in the transposition table.
+    pub tt_misses: u64,
+    /// Positions where a move failed high, cutting off the search.
+    pub cutoffs: u64,
+    /// Cutoffs caused by the first move searched.
+    pub first_move_cutoffs: u64,
+    /// The deepest ply reached, counting quiescence search.
+    pub seldepth: usize,
+    /// Root searches that scored above their aspiration window.
+    pub aspiration_fail_highs: u64,
+    /// Root searches that scored below their aspiration window.
+    pub aspiration_fail_lows: u64,
+}
+
+impl SearchStats {
+    /// The fraction of cutoffs caused by the first move searched, which is
+    /// close to one when the moves are well ordered.
+    pub fn first_move_cutoff_ratio(&self) -> f64 {
+        if self.cutoffs == 0 {
+            return 0.0;
+        }
+        self.first_move_cutoffs as f64 / self.cutoffs as f64
+    }
+}
+
+/// Move the table's best move, if any, to the front of the list so it is
+/// searched first.
+pub(crate) fn order_moves(moves: &mut [Move], best_move: Option<Move>) {
+    if let Some(i) = best_move.and_then(|best| moves.iter().position(|m| *m == best)) {
+        moves[..=i].rotate_right(1);
+    }
+}
+
+/// The material a capture takes plus what a promotion adds, valued by
+/// `Evaluate::get_material`, or `None` for a quiet move.
+pub(crate) fn material_gain<E: Evaluate>(board: &E, m: Move) -> Option<f64> {
+    let captured = board.get_captured_piece(m).map(|piece| board.get_material(&piece));
+    let promoted = match m {
+        Move::Promotion(from, _, piece) => {
+            Some(board.get_material(&piece) - board.get_material(&Piece::Pawn(piece.get_color(), from)))
+        }
+        _ => None,
+    };
+    if captured.is_none() && promoted.is_none() {
+        return None;
+    }
+    Some(captured.unwrap_or(0.0) + promoted.unwrap_or(0.0))
+}
+
+/// The most an attacker's value can weigh in a `capture_score`.
+const MAX_ATTACKER_SCORE: i32 = 1023;
+
+/// The MVV-LVA score of a capture or promotion, highest for the most
+/// valuable victim taken by the least valuable attacker. Quiet moves have
+/// no score.
+pub(crate) fn capture_score<E: Evaluate>(board: &E, m: Move) -> Option<i32> {
+    let victim = material_gain(board, m)? as i32;
+    // a king is the least willing attacker, whatever it is worth
+    let attacker = match board.get_moving_piece(m) {
+        Some(piece) if piece.is_king() => MAX_ATTACKER_SCORE,
+        Some(piece) => (board.get_material(&piece) as i32).clamp(0, MAX_ATTACKER_SCORE),
+        None => 0,
+    };
+    Some(victim * (MAX_ATTACKER_SCORE + 1) - attacker)
+}
+
+/// Sort captures and promotions by their MVV-LVA score.
+pub(crate) fn order_captures<E: Evaluate>(board: &E, moves: &mut [Move]) {
+    moves.sort_by_cached_key(|m| Reverse(capture_score(board, *m)));
+}
+
+/// Captures and promotions are searched before every quiet move.
+const CAPTURE_SCORE: i64 = 1 << 40;
+/// Killer moves are searched before the quiet moves ordered by history.
+const KILLER_SCORE: i64 = 1 << 36;
+
+/// The squares a move goes from and to, used to index the history table.
+fn history_index(m: Move) -> Option<(usize, usize)> {
+    match m {
+        Move::Piece(from, to) | Move::Promotion(from, to, _) => Some((
+            (from.get_row() * 8 + from.get_col()) as usize,
+            (to.get_row() * 8 + to.get_col()) as usize,
+        )),
+        _ => None,
+    }
+}
+
+/// The depth searched when no limits are given at all.
+pub const DEFAULT_DEPTH: i32 = 4;
+
+/// The deepest iteration started when only time or node limits are given.
+pub const MAX_DEPTH: i32 = 64;
+
+/// The fraction of the remaining clock time spent on a single move.
+const MOVES_TO_GO: u64 = 30;
+
+/// How long to think when only the other player's clock is given, since
+/// the clocks still call for a timed search.
+const MISSING_CLOCK_MOVETIME: u64 = 1000;
+
+/// When to stop deepening the search. Times are in milliseconds.
+///
+/// The search itself only enforces `depth` and `nodes`, since it cannot
+/// read a clock. Time limits are up to the `SearchControl`, which can use
+/// `time_budget` to decide when to stop.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// The deepest iteration to search, in plies.
+    pub depth: Option<i32>,
+    /// Stop once this many positions have been visited. Quiescence search
+    /// can run slightly past the limit.
+    pub nodes: Option<u64>,
+    /// Think for exactly this long.
+    pub movetime: Option<u64>,
+    /// White's remaining clock time.
+    pub wtime: Option<u64>,
+    /// Black's remaining clock time.
+    pub btime: Option<u64>,
+    /// White's increment per move.
+    pub winc: u64,
+    /// Black's increment per move.
+    pub binc: u64,
+    /// Search until told to stop.
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    /// Limit the search to a fixed depth.
+    pub fn depth(depth: i32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    /// How long the given color may think about its move, if the time is
+    /// limited at all.
+    pub fn time_budget(&self, color: Color) -> Option<u64> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime);
+        }
+        let (time, inc) = match color {
+            WHITE => (self.wtime, self.winc),
+            BLACK => (self.btime, self.binc),
+        };
+        match time {
+            Some(time) => Some((time / MOVES_TO_GO + inc / 2).min(time)),
+            None if self.wtime.is_some() || self.btime.is_some() => Some(MISSING_CLOCK_MOVETIME),
+            None => None,
+        }
+    }
+
+    /// The deepest iteration to start.
+    pub fn max_depth(&self) -> i32 {
+        match self.depth {
+            Some(depth) => depth.max(1),
+            None if self.infinite || self.nodes.is_some() || self.movetime.is_some() => MAX_DEPTH,
+            None if self.wtime.is_some() || self.btime.is_some() => MAX_DEPTH,
+            None => DEFAULT_DEPTH,
+        }
+    }
+}
+
+/// The outcome of a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// The best move found, or `Move::Resign` when there are no legal moves.
+    pub best_move: Move,
+    /// The line of play expected after the best move, starting with it.
+    pub pv: Vec<Move>,
+    /// The value of the best move for the player to move.
+    pub value: Score,
+    /// The depth of the last completed iteration, in plies.
+    pub depth: i32,
+    /// Counters over every iteration so far.
+    pub stats: SearchStats,
+}
+
+/// Lets the caller of `Evaluate::search` stop it early and follow its
+/// progress. Any `FnMut() -> bool` closure works as a control that stops
+/// the search once it returns `true`.
+pub trait SearchControl {
+    /// Should the search be aborted? This is polled every few thousand
+    /// positions. The first iteration always runs to completion, so there
+    /// is always a move to play.
+    fn should_stop(&mut self) -> bool;
+
+    /// Called after every completed iteration. Returning `false` stops the
+    /// search without starting the next one.
+    fn on_iteration(&mut self, _result: &SearchResult) -> bool { true }
+
+    /// Called when an iteration's value falls outside its aspiration
+    /// window, before the root is searched again with a wider one. The
+    /// value is only a bound: `Bound::Lower` after failing high and
+    /// `Bound::Upper` after failing low.
+    fn on_aspiration_fail(&mut self, _result: &SearchResult, _bound: Bound) {}
+
+    /// Called after every completed iteration of a multi-PV search with
+    /// each line found, best first. By default this reports the best line
+    /// to `on_iteration`.
+    fn on_lines(&mut self, lines: &[SearchResult]) -> bool { self.on_iteration(&lines[0]) }
+}
+
+impl<F: FnMut() -> bool> SearchControl for F {
+    fn should_stop(&mut self) -> bool { self() }
+}
+
+/// Which refinements of plain alpha-beta the search uses. Each can be
+/// switched off on its own to measure what it is worth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// After the table's best move, try captures by MVV-LVA, then killer
+    /// moves, then quiet moves by their history. Without it, the rest of
+    /// the moves are searched in the order they were generated.
+    pub move_ordering: bool,
+    /// Principal variation search: every move after the first is searched
+    /// with a null window, and again with the full window only if it turns
+    /// out to be better.
+    pub pvs: bool,
+    /// Let the opponent move twice in a row with a shallower search, and
+    /// cut off if they still can't get below beta. Never used in check or
+    /// when `Evaluate::apply_null_move` declines.
+    pub null_move: bool,
+    /// Search quiet moves late in the move order less deeply, and again at
+    /// full depth if they beat alpha anyway.
+    pub late_move_reductions: bool,
+    /// Search one ply deeper when in check.
+    pub check_extensions: bool,
+    /// Search each iteration with a narrow window around the value of the
+    /// last one, widening it whenever the value falls outside.
+    pub aspiration_windows: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            move_ordering: true,
+            pvs: true,
+            null_move: true,
+            late_move_reductions: true,
+            check_extensions: true,
+            aspiration_windows: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Plain alpha-beta search, with every refinement switched off.
+    pub fn plain() -> Self {
+        Self {
+            move_ordering: false,
+            pvs: false,
+            null_move: false,
+            late_move_reductions: false,
+            check_extensions: false,
+            aspiration_windows: false,
+        }
+    }
+}
+
+/// How many plies shallower the position after a null move is searched.
+const NULL_MOVE_REDUCTION: i32 = 2;
+/// The shallowest depth where a null move is tried.
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+/// The shallowest depth where late moves are reduced.
+const LMR_MIN_DEPTH: i32 = 3;
+/// How many moves are searched at full depth before reducing the rest.
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+/// The shallowest iteration searched with an aspiration window.
+const ASPIRATION_MIN_DEPTH: i32 = 4;
+/// How far the first aspiration window reaches either side of the last
+/// iteration's value, in centipawns. It doubles after every failure.
+const ASPIRATION_WINDOW: i32 = 50;
+
+/// How many positions are visited between polls of `should_stop`.
+const POLL_INTERVAL: u64 = 1024;
+
+/// The state shared by every node of a search.
+pub(crate) struct Searcher<'a, C: SearchControl> {
+    table: &'a mut TranspositionTable,
+    control: &'a mut C,
+    node_limit: Option<u64>,
+    config: SearchConfig,
+    pub(crate) stats: SearchStats,
+    /// Whether the current iteration may be abandoned part way through.
+    can_abort: bool,
+    aborted: bool,
+    /// The last two quiet moves to cause a cutoff at each ply.
+    killers: Vec<[Option<Move>; 2]>,
+    /// How often each quiet move caused a cutoff, weighted by depth and
+    /// indexed by the squares it goes from and to.
+    history: Vec<[u32; 64]>,
+    /// The best line found from each ply, each built from the one below.
+    pv: Vec<Vec<Move>>,
+    /// Whether the position being entered was reached by a null move, so
+    /// another one isn't tried straight away.
+    after_null_move: bool,
+}
+
+impl<'a, C: SearchControl> Searcher<'a, C> {
+    pub(crate) fn new(
+        table: &'a mut TranspositionTable,
+        control: &'a mut C,
+        node_limit: Option<u64>,
+        config: SearchConfig,
+    ) -> Self {
+        Self {
+            table,
+            control,
+            node_limit,
+            config,
+            stats: SearchStats::default(),
+            can_abort: false,
+            aborted: false,
+            killers: Vec::new(),
+            history: vec![[0; 64]; 64],
+            pv: Vec::new(),
+            after_null_move: false,
+        }
+    }
+
+    /// Start a new line at this ply, forgetting the one from the last
+    /// position searched here.
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv.len() <= ply + 1 {
+            self.pv.resize_with(ply + 2, Vec::new);
+        }
+        self.pv[ply].clear();
+    }
+
+    /// Make the best line at this ply the move followed by the line below.
+    fn update_pv(&mut self, ply: usize, m: Move) {
+        let (line, rest) = self.pv[ply..].split_at_mut(1);
+        line[0].clear();
+        line[0].push(m);
+        line[0].extend_from_slice(&rest[0]);
+    }
+
+    /// The best line from the root after the last completed search of the
+    /// given number of plies. Where the line was cut short by a position
+    /// found in the table, it is continued with the table's best moves.
+    pub(crate) fn principal_variation<E: Evaluate>(&self, board: &E, plies: i32) -> Vec<Move> {
+        let mut pv = self.pv.first().cloned().unwrap_or_default();
+        let mut position = None;
+        for m in &pv {
+            position = Some(position.as_ref().unwrap_or(board).apply_eval_move(*m));
+        }
+
+        while pv.len() < plies as usize {
+            let current = position.as_ref().unwrap_or(board);
+            let m = match self.table.get(current.get_hash()).and_then(|entry| entry.best_move) {
+                Some(m) if current.get_legal_moves().contains(&m) => m,
+                _ => break,
+            };
+            pv.push(m);
+            position = Some(current.apply_eval_move(m));
+        }
+        pv
+    }
+
+    /// Sort the moves so those likeliest to cause a cutoff come first: the
+    /// table's best move, then captures and promotions by MVV-LVA, then the
+    /// killer moves for this ply, then the rest by their history.
+    fn order_moves<E: Evaluate>(&self, board: &E, moves: &mut [Move], table_move: Option<Move>, ply: usize) {
+        if !self.config.move_ordering {
+            return order_moves(moves, table_move);
+        }
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|m| {
+            Reverse(match capture_score(board, *m) {
+                Some(score) => CAPTURE_SCORE + score as i64,
+                None if killers[0] == Some(*m) => KILLER_SCORE + 1,
+                None if killers[1] == Some(*m) => KILLER_SCORE,
+                None => history_index(*m).map_or(0, |(from, to)| self.history[from][to] as i64),
+            })
+        });
+        order_moves(moves, table_move);
+    }
+
+    /// Remember a quiet move that caused a cutoff, so it is tried early in
+    /// its sibling positions and elsewhere in the tree.
+    fn store_cutoff(&mut self, m: Move, depth: i32, ply: usize) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+
+        if let Some((from, to)) = history_index(m) {
+            let history = &mut self.history[from][to];
+            *history = history.saturating_add((depth * depth) as u32);
+        }
+    }
+
+    fn should_abort(&mut self) -> bool {
+        if self.can_abort && !self.aborted {
+            let nodes = self.stats.board_count;
+            self.aborted = self.node_limit.is_some_and(|limit| nodes >= limit)
+                || (nodes.is_multiple_of(POLL_INTERVAL) && self.control.should_stop());
+        }
+        self.aborted
+    }
+
+    /// Search every move from the root but the `excluded` ones to the given
+    /// number of plies within the window from `alpha` to `beta`, returning
+    /// the best one and its value, or `None` if the search was aborted
+    /// before finishing. A value outside the window is only a bound on the
+    /// true one.
+    pub(crate) fn search_root<E: Evaluate>(
+        &mut self,
+        board: &E,
+        plies: i32,
+        mut alpha: Score,
+        beta: Score,
+        excluded: &[Move],
+    ) -> Option<(Move, Score)> {
+        let mut legal_moves = board.get_legal_moves();
+        legal_moves.retain(|m| !excluded.contains(m));
+        let hash = board.get_hash();
+        let table_move = self.table.get(hash).and_then(|entry| entry.best_move);
+        self.order_moves(board, &mut legal_moves, table_move, 0);
+        self.clear_pv(0);
+
+        if legal_moves.is_empty() {
+            let value = if board.is_current_player_in_check() { Score::mated_in(0) } else { Score::DRAW };
+            return Some((Move::Resign, value));
+        }
+
+        let mut best_move_value = -Score::INFINITE;
+        let mut best_move = Move::Resign;
+        let original_alpha = alpha;
+        // an aborted iteration mustn't overwrite the last complete line
+        let mut pv = Vec::new();
+
+        for (i, m) in legal_moves.iter().enumerate() {
+            let child = board.apply_eval_move(*m);
+            let child_board_value = if i == 0 {
+                -self.negamax(&child, plies - 1, 1, -beta, -alpha)
+            } else {
+                self.search_later_move(&child, plies - 1, 0, 1, alpha, beta)
+            };
+            if self.aborted {
+                return None;
+            }
+            if child_board_value > best_move_value || best_move == Move::Resign {
+                best_move = *m;
+                best_move_value = child_board_value;
+                pv.clear();
+                pv.push(*m);
+                pv.extend_from_slice(&self.pv[1]);
+            }
+            if best_move_value > alpha {
+                alpha = best_move_value;
+            }
+            if beta <= alpha {
+                break;
+            }
+        }
+
+        // the table keeps the best move, not the best of those left over
+        if best_move != Move::Resign && excluded.is_empty() {
+            let bound = if best_move_value <= original_alpha {
+                Bound::Upper
+            } else if best_move_value >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.insert(TableEntry {
+                hash,
+                depth: plies,
+                value: best_move_value,
+                bound,
+                best_move: Some(best_move),
+            });
+        }
+
+        self.pv[0] = pv;
+        Some((best_move, best_move_value))
+    }
+
+    /// Search the root with a window around the value of the last
+    /// iteration, if there is one, and search again with the window
+    /// widened on whichever side the value fell outside it.
+    fn search_aspiration<E: Evaluate>(
+        &mut self,
+        board: &E,
+        plies: i32,
+        last_value: Option<Score>,
+        excluded: &[Move],
+    ) -> Option<(Move, Score)> {
+        let mut delta = Score::centipawns(ASPIRATION_WINDOW);
+        let (mut alpha, mut beta) = match last_value {
+            // a mate score swings too far between iterations to aim at
+            Some(value) if self.config.aspiration_windows && plies >= ASPIRATION_MIN_DEPTH && !value.is_mate() => {
+                (value - delta, value + delta)
+            }
+            _ => (-Score::INFINITE, Score::INFINITE),
+        };
+
+        loop {
+            let (best_move, value) = self.search_root(board, plies, alpha, beta, excluded)?;
+            let bound = if best_move == Move::Resign {
+                return Some((best_move, value));
+            } else if value <= alpha {
+                self.stats.aspiration_fail_lows += 1;
+                alpha = if value.is_mate() { -Score::INFINITE } else { (value - delta).max(-Score::INFINITE) };
+                Bound::Upper
+            } else if value >= beta {
+                self.stats.aspiration_fail_highs += 1;
+                beta = if value.is_mate() { Score::INFINITE } else { (value + delta).min(Score::INFINITE) };
+                Bound::Lower
+            } else {
+                return Some((best_move, value));
+            };
+            delta = delta + delta;
+
+            let result = SearchResult {
+                best_move,
+                pv: self.principal_variation(board, plies),
+                value,
+                depth: plies,
+                stats: self.stats,
+            };
+            self.control.on_aspiration_fail(&result, bound);
+        }
+    }
+
+    /// Search the position after any move but the first, returning its
+    /// value for the player who made it. With PVS, the move is searched
+    /// with a null window first. A reduced move is searched again at full
+    /// depth if it beats alpha.
+    fn search_later_move<E: Evaluate>(
+        &mut self,
+        child: &E,
+        depth: i32,
+        reduction: i32,
+        ply: usize,
+        alpha: Score,
+        beta: Score,
+    ) -> Score {
+        if !self.config.pvs && reduction == 0 {
+            return -self.negamax(child, depth, ply, -beta, -alpha);
+        }
+
+        let null_window = alpha + Score::centipawns(1);
+        let mut value = -self.negamax(child, depth - reduction, ply, -null_window, -alpha);
+        if value > alpha && reduction > 0 && self.config.pvs {
+            value = -self.negamax(child, depth, ply, -null_window, -alpha);
+        }
+        // without PVS, a reduced move that beats alpha gets a full search
+        if value > alpha && (value < beta || !self.config.pvs) {
+            value = -self.negamax(child, depth, ply, -beta, -alpha);
+        }
+        value
+    }
+
+    /// Alpha-beta search returning the value of a position for the player
+    /// to move, `ply` moves from the root, storing every result in the
+    /// table.
+    fn negamax<E: Evaluate>(&mut self, board: &E, mut depth: i32, ply: usize, mut alpha: Score, beta: Score) -> Score {
+        self.stats.board_count += 1;
+        let after_null_move = core::mem::take(&mut self.after_null_move);
+        if self.should_abort() {
+            return Score::DRAW;
+        }
+
+        self.clear_pv(ply);
+        let in_check = board.is_current_player_in_check();
+        if in_check && self.config.check_extensions {
+            depth += 1;
+        }
+        if depth <= 0 {
+            return board.quiescence(alpha, beta, ply, &mut self.stats);
+        }
+
+        let hash = board.get_hash();
+        let mut table_move = None;
+        match self.table.get(hash) {
+            Some(entry) => {
+                self.stats.tt_hits += 1;
+                let value = entry.value.add_plies(ply as i32);
+                if entry.depth >= depth {
+                    match entry.bound {
+                        Bound::Exact => return value,
+                        Bound::Lower if value >= beta => return value,
+                        Bound::Upper if value <= alpha => return value,
+                        _ => {}
+                    }
+                }
+                table_move = entry.best_move;
+            }
+            None => self.stats.tt_misses += 1,
+        }
+
+        // if passing still holds the opponent below beta, a real move will too
+        if self.config.null_move && !in_check && !after_null_move && depth >= NULL_MOVE_MIN_DEPTH {
+            if let Some(null_board) = board.apply_null_move() {
+                self.after_null_move = true;
+                let value = -self.negamax(
+                    &null_board,
+                    depth - 1 - NULL_MOVE_REDUCTION,
+                    ply + 1,
+                    -beta,
+                    -beta + Score::centipawns(1),
+                );
+                if self.aborted {
+                    return Score::DRAW;
+                }
+                if value >= beta {
+                    // a mate found after passing can't be trusted
+                    return if value.is_mate() { beta } else { value };
+                }
+            }
+        }
+
+        let mut legal_moves = board.get_legal_moves();
+        if legal_moves.is_empty() {
+            return if in_check { Score::mated_in(ply as i32) } else { Score::DRAW };
+        }
+        self.order_moves(board, &mut legal_moves, table_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_move_value = -Score::INFINITE;
+        let mut best_move = None;
+        for (i, m) in legal_moves.into_iter().enumerate() {
+            let child = board.apply_eval_move(m);
+            let quiet = capture_score(board, m).is_none();
+            let reduction = if self.config.late_move_reductions
+                && depth >= LMR_MIN_DEPTH
+                && i >= LMR_FULL_DEPTH_MOVES
+                && quiet
+                && !in_check
+                && !child.is_current_player_in_check()
+            {
+                1
+            } else {
+                0
+            };
+
+            let child_board_value = if i == 0 {
+                -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                self.search_later_move(&child, depth - 1, reduction, ply + 1, alpha, beta)
+            };
+            if self.aborted {
+                // the value is meaningless, so don't store it
+                return Score::DRAW;
+            }
+
+            if child_board_value > best_move_value {
+                best_move_value = child_board_value;
+                best_move = Some(m);
+            }
+
+            if best_move_value > alpha {
+                alpha = best_move_value;
+                self.update_pv(ply, m);
+            }
+
+            if beta <= alpha {
+                self.stats.cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                if quiet {
+                    self.store_cutoff(m, depth, ply);
+                }
+                break;
+            }
+        }
+
+        let bound = if best_move_value <= original_alpha {
+            Bound::Upper
+        } else if best_move_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(TableEntry {
+            hash,
+            depth,
+            value: best_move_value.add_plies(-(ply as i32)),
+            bound,
+            best_move,
+        });
+
+        best_move_value
+    }
+}
+
+/// Search one ply deeper at a time until a limit is reached, keeping the
+/// best `lines` moves of the last completed iteration, best first. Each
+/// line is found by searching the root again without the moves of the
+/// lines before it.
+pub(crate) fn iterative_deepening<E: Evaluate, C: SearchControl>(
+    board: &E,
+    limits: &SearchLimits,
+    config: SearchConfig,
+    lines: usize,
+    table: &mut TranspositionTable,
+    control: &mut C,
+) -> Vec<SearchResult> {
+    let lines = lines.clamp(1, board.get_legal_moves().len().max(1));
+    let mut searcher = Searcher::new(table, control, limits.nodes, config);
+    let mut results = vec![SearchResult {
+        best_move: Move::Resign,
+        pv: Vec::new(),
+        value: Score::DRAW,
+        depth: 0,
+        stats: SearchStats::default(),
+    }];
+
+    'deepening: for depth in 1..=limits.max_depth() {
+        searcher.can_abort = depth > 1;
+        if searcher.can_abort && searcher.control.should_stop() {
+            break;
+        }
+
+        let mut iteration: Vec<SearchResult> = Vec::with_capacity(lines);
+        for line in 0..lines {
+            let last_value = results.get(line).filter(|result| result.depth > 0).map(|result| result.value);
+            let excluded = iteration.iter().map(|result| result.best_move).collect::<Vec<_>>();
+            let (best_move, value) = match searcher.search_aspiration(board, depth, last_value, &excluded) {
+                Some(best) => best,
+                None => break 'deepening,
+            };
+            iteration.push(SearchResult {
+                best_move,
+                pv: searcher.principal_variation(board, depth),
+                value,
+                depth,
+                stats: searcher.stats,
+            });
+        }
+
+        // a later line can come out ahead when pruning misjudged it first
+        iteration.sort_by_key(|result| Reverse(result.value));
+        for result in &mut iteration {
+            result.stats = searcher.stats;
+        }
+        results = iteration;
+        if results[0].best_move == Move::Resign || !searcher.control.on_lines(&results) {
+            break;
+        }
+    }
+
+    for result in &mut results {
+        result.stats = searcher.stats;
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_fen, Board, Piece, Position, A1, A2, A3, A7, A8, B4, C5, C7, D3, D6, E5, H1, H2, H8};
+    use alloc::vec::Vec;
+
+    const POSITIONS: [&str; 3] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    #[test]
+    fn test_table_matches_minimax() {
+        for fen in POSITIONS {
+            let board = parse_fen(fen).unwrap();
+            let color = board.get_turn_color();
+            for depth in 1..4 {
+                let mut count = 0;
+                let expected = board.minimax(depth, -Score::INFINITE, Score::INFINITE, true, color, &mut count);
+
+                // PVS only changes how the value is found, not what it is
+                let pvs = SearchConfig {
+                    pvs: true,
+                    ..SearchConfig::plain()
+                };
+                for config in [SearchConfig::plain(), pvs] {
+                    let mut table = TranspositionTable::new(1);
+                    let mut never_stop = || false;
+                    let mut searcher = Searcher::new(&mut table, &mut never_stop, None, config);
+                    let (_, value) = searcher.search_root(&board, depth, -Score::INFINITE, Score::INFINITE, &[]).unwrap();
+                    assert_eq!(value, expected, "{} at depth {}", fen, depth);
+                    if config == SearchConfig::plain() {
+                        assert!(searcher.stats.board_count <= count);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_table_hits() {
+        let board = Board::default();
+        let mut table = TranspositionTable::new(1);
+
+        let (_, first, _) = board.get_best_next_move_with_table(3, &mut table);
+        assert!(first.tt_hits > 0);
+        assert!(first.tt_misses > 0);
+
+        // the second search reuses the stored positions
+        let (_, second, _) = board.get_best_next_move_with_table(3, &mut table);
+        assert!(second.board_count < first.board_count);
+        assert!(table.get(board.hash()).is_some());
+        assert!(table.hashfull() > 0);
+
+        table.clear();
+        assert!(table.get(board.hash()).is_none());
+    }
+
+    #[test]
+    fn test_iterative_deepening() {
+        let board = parse_fen(POSITIONS[1]).unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = board.search(&SearchLimits::depth(3), &mut table, &mut || false);
+        assert_eq!(result.depth, 3);
+        assert!(board.get_legal_moves().contains(&result.best_move));
+        assert!(table.get(board.hash()).is_some_and(|entry| entry.depth == 3));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let board = Board::default();
+        let limits = SearchLimits {
+            nodes: Some(2000),
+            ..SearchLimits::default()
+        };
+        let result = board.search(&limits, &mut TranspositionTable::new(1), &mut || false);
+        assert!((2000..2500).contains(&result.stats.board_count));
+        assert!(result.depth >= 2 && result.depth < MAX_DEPTH);
+        assert!(board.get_legal_moves().contains(&result.best_move));
+        // the aborted iteration doesn't leave its line behind
+        assert_eq!(result.pv.first(), Some(&result.best_move));
+    }
+
+    #[test]
+    fn test_principal_variation() {
+        for fen in POSITIONS {
+            let board = parse_fen(fen).unwrap();
+            let result = board.search(&SearchLimits::depth(4), &mut TranspositionTable::new(1), &mut || false);
+            // check extensions can make the line longer
+            assert!(result.pv.len() >= 4, "{}", fen);
+            assert_eq!(result.pv[0], result.best_move);
+            assert!(result.stats.seldepth >= 4);
+
+            let mut position = board;
+            for m in &result.pv {
+                assert!(position.get_legal_moves().contains(m), "{} in {}", m, fen);
+                position = position.apply_eval_move(*m);
+            }
+        }
+
+        // the line ends at checkmate
+        let board = parse_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = board.search(&SearchLimits::depth(3), &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(result.pv, [Move::Piece(A1, A8)]);
+    }
+
+    #[test]
+    fn test_search_control() {
+        // the first iteration always completes, even when told to stop
+        let board = Board::default();
+        let limits = SearchLimits::depth(5);
+        let result = board.search(&limits, &mut TranspositionTable::new(1), &mut || true);
+        assert_eq!(result.depth, 1);
+        assert!(board.get_legal_moves().contains(&result.best_move));
+
+        struct StopAtDepth(i32, Vec<i32>);
+        impl SearchControl for StopAtDepth {
+            fn should_stop(&mut self) -> bool { false }
+
+            fn on_iteration(&mut self, result: &SearchResult) -> bool {
+                self.1.push(result.depth);
+                result.depth < self.0
+            }
+        }
+        let mut control = StopAtDepth(3, Vec::new());
+        let result = board.search(&limits, &mut TranspositionTable::new(1), &mut control);
+        assert_eq!(result.depth, 3);
+        assert_eq!(control.1, [1, 2, 3]);
+
+        let mated = parse_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let result = mated.search(&limits, &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(result.best_move, Move::Resign);
+        assert_eq!(result.value, Score::mated_in(0));
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let limits = SearchLimits {
+            wtime: Some(60000),
+            btime: Some(1000),
+            winc: 1000,
+            ..SearchLimits::default()
+        };
+        assert_eq!(limits.time_budget(WHITE), Some(2500));
+        assert_eq!(limits.time_budget(BLACK), Some(33));
+        assert_eq!(limits.max_depth(), MAX_DEPTH);
+
+        let limits = SearchLimits {
+            movetime: Some(500),
+            ..limits
+        };
+        assert_eq!(limits.time_budget(BLACK), Some(500));
+        assert_eq!(SearchLimits::default().time_budget(WHITE), None);
+        assert_eq!(SearchLimits::default().max_depth(), DEFAULT_DEPTH);
+        assert_eq!(SearchLimits::depth(0).max_depth(), 1);
+
+        // without its own clock, the player to move still has a budget
+        let limits = SearchLimits {
+            btime: Some(60000),
+            ..SearchLimits::default()
+        };
+        assert_eq!(limits.time_budget(WHITE), Some(MISSING_CLOCK_MOVETIME));
+        assert_eq!(limits.time_budget(BLACK), Some(2000));
+    }
+
+    #[test]
+    fn test_replace_by_depth() {
+        let mut table = TranspositionTable::new(0);
+        assert_eq!(table.capacity(), 1);
+
+        let entry = TableEntry {
+            hash: 1,
+            depth: 3,
+            value: Score::DRAW,
+            bound: Bound::Exact,
+            best_move: Some(Move::Piece(A1, A2)),
+        };
+        table.insert(entry);
+        table.insert(TableEntry {
+            hash: 2,
+            depth: 2,
+            ..entry
+        });
+        assert_eq!(table.get(1), Some(&entry));
+        assert_eq!(table.get(2), None);
+
+        let deeper = TableEntry {
+            hash: 2,
+            depth: 4,
+            best_move: Some(Move::Promotion(A1, A2, Piece::Queen(WHITE, A2))),
+            ..entry
+        };
+        table.insert(deeper);
+        assert_eq!(table.get(1), None);
+        assert_eq!(table.get(2), Some(&deeper));
+    }
+
+    #[test]
+    fn test_table_size() {
+        let default = TranspositionTable::default().capacity();
+        assert_eq!(TranspositionTable::with_capacity(0).capacity(), 1);
+        assert_eq!(TranspositionTable::for_depth(2).capacity(), 1 << 12);
+        assert!(TranspositionTable::for_depth(4).capacity() < default);
+        assert_eq!(TranspositionTable::for_depth(20).capacity(), default);
+    }
+
+    #[test]
+    fn test_capture_order() {
+        let board = parse_fen("4k3/8/8/2q1r3/1P6/3N4/8/Q6K w - - 0 1").unwrap();
+        let mut moves = board.get_noisy_moves();
+        order_captures(&board, &mut moves);
+        assert_eq!(
+            moves,
+            [
+                Move::Piece(B4, C5),
+                Move::Piece(D3, C5),
+                Move::Piece(D3, E5),
+                Move::Piece(A1, E5),
+            ]
+        );
+        assert_eq!(capture_score(&board, Move::Piece(H1, H2)), None);
+
+        let board = parse_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let queen = Move::Promotion(A7, A8, Piece::Queen(WHITE, Position::new(-1, -1)));
+        let knight = Move::Promotion(A7, A8, Piece::Knight(WHITE, Position::new(-1, -1)));
+        assert!(capture_score(&board, queen) > capture_score(&board, knight));
+    }
+
+    #[test]
+    fn test_killers_and_history() {
+        let board = parse_fen("4k3/8/8/2q1r3/1P6/3N4/8/Q6K w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let mut never_stop = || false;
+        let mut searcher = Searcher::new(&mut table, &mut never_stop, None, SearchConfig::default());
+
+        let killer = Move::Piece(H1, H2);
+        let quiet = Move::Piece(A1, A3);
+        searcher.store_cutoff(quiet, 1, 0);
+        searcher.store_cutoff(killer, 1, 2);
+        let mut moves = board.get_legal_moves();
+        searcher.order_moves(&board, &mut moves, None, 2);
+        assert_eq!(moves[4], killer);
+        // the move without a killer at this ply comes first by its history
+        assert_eq!(moves[5], quiet);
+
+        let mut moves = board.get_legal_moves();
+        searcher.order_moves(&board, &mut moves, Some(quiet), 2);
+        assert_eq!(moves[..2], [quiet, Move::Piece(B4, C5)]);
+    }
+
+    #[test]
+    fn test_first_move_cutoffs() {
+        let board = parse_fen(POSITIONS[1]).unwrap();
+        let result = board.search(&SearchLimits::depth(5), &mut TranspositionTable::new(1), &mut || false);
+        let stats = result.stats;
+        assert!(stats.cutoffs > 0);
+        assert!(stats.first_move_cutoffs <= stats.cutoffs);
+        assert!(stats.first_move_cutoff_ratio() > 0.7);
+        // ordering beats searching the moves after the table's in the order
+        // they were generated
+        let unordered = SearchConfig { move_ordering: false, ..SearchConfig::default() };
+        let unordered = board.search_with_config(&SearchLimits::depth(5), unordered, &mut TranspositionTable::new(1), &mut || false);
+        assert!(stats.board_count < unordered.stats.board_count);
+        assert!(stats.first_move_cutoff_ratio() > unordered.stats.first_move_cutoff_ratio());
+        assert_eq!(SearchStats::default().first_move_cutoff_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_mate_scores() {
+        // Rh8 mates at once, but the rook has plenty of slower mates too
+        let board = parse_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = board.search(&SearchLimits::depth(5), &mut table, &mut || false);
+        assert_eq!(result.best_move, Move::Piece(H1, H8));
+        assert_eq!(result.value, Score::mate_in(1));
+        assert_eq!(result.value.get_mate(), Some(1));
+        // the stored mate is still counted from the root when found again
+        let result = board.search(&SearchLimits::depth(5), &mut table, &mut || false);
+        assert_eq!(result.value, Score::mate_in(1));
+
+        // Kg8 is forced, then Qc8 mates
+        let board = parse_fen("7k/8/6K1/5Q2/8/8/8/8 b - - 0 1").unwrap();
+        let result = board.search(&SearchLimits::depth(3), &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(result.value.get_mate(), Some(-1));
+
+        let (_, _, value) = board.get_best_next_move(2);
+        assert_eq!(value, Score::mated_in(2));
+        let mut count = 0;
+        let value = board.minimax(3, -Score::INFINITE, Score::INFINITE, true, BLACK, &mut count);
+        assert_eq!(value, Score::mated_in(2));
+    }
+
+    #[test]
+    fn test_stalemate() {
+        let board = parse_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        let result = board.search(&SearchLimits::depth(3), &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(result.best_move, Move::Resign);
+        assert_eq!(result.value, Score::DRAW);
+    }
+
+    #[test]
+    fn test_search_config() {
+        let board = parse_fen(POSITIONS[1]).unwrap();
+        let limits = SearchLimits::depth(5);
+        let search = |config| board.search_with_config(&limits, config, &mut TranspositionTable::new(1), &mut || false);
+        let plain = SearchConfig::plain();
+        let plain_count = search(plain).stats.board_count;
+        let configs = [
+            SearchConfig { move_ordering: true, ..plain },
+            SearchConfig { pvs: true, ..plain },
+            SearchConfig { null_move: true, ..plain },
+            SearchConfig { late_move_reductions: true, ..plain },
+            SearchConfig { check_extensions: true, ..plain },
+            SearchConfig { aspiration_windows: true, ..plain },
+        ];
+        for config in configs {
+            let result = search(config);
+            assert_eq!(result.depth, 5);
+            assert!(board.get_legal_moves().contains(&result.best_move), "{:?}", config);
+            // check extensions search deeper instead of pruning, so they add positions
+            if !config.check_extensions {
+                assert!(result.stats.board_count < plain_count, "{:?}", config);
+            }
+        }
+
+        let all = board.search(&limits, &mut TranspositionTable::new(1), &mut || false);
+        assert!(all.stats.board_count < plain_count);
+    }
+
+    #[test]
+    fn test_check_extensions() {
+        // Qc7+ Ka8 Qb7 mates, but the last move is past the horizon
+        let board = parse_fen("8/k7/2KQ4/8/8/8/8/8 w - - 0 1").unwrap();
+        let limits = SearchLimits::depth(2);
+        let plain = SearchConfig::plain();
+        let result = board.search_with_config(&limits, plain, &mut TranspositionTable::new(1), &mut || false);
+        assert!(!result.value.is_mate());
+
+        let extended = SearchConfig { check_extensions: true, ..plain };
+        let result = board.search_with_config(&limits, extended, &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(result.value, Score::mate_in(3));
+        assert_eq!(result.best_move, Move::Piece(D6, C7));
+    }
+
+    /// Records the bound of every aspiration window failure.
+    struct AspirationFails(Vec<Bound>);
+
+    impl SearchControl for AspirationFails {
+        fn should_stop(&mut self) -> bool { false }
+
+        fn on_aspiration_fail(&mut self, result: &SearchResult, bound: Bound) {
+            assert!(!result.pv.is_empty());
+            self.0.push(bound);
+        }
+    }
+
+    #[test]
+    fn test_aspiration_windows() {
+        // without pruning, a narrow window only changes how the value is found
+        let plain = SearchConfig::plain();
+        let aspiration = SearchConfig { aspiration_windows: true, ..plain };
+        for fen in POSITIONS {
+            let board = parse_fen(fen).unwrap();
+            let limits = SearchLimits::depth(5);
+            let expected = board.search_with_config(&limits, plain, &mut TranspositionTable::new(1), &mut || false);
+            let result = board.search_with_config(&limits, aspiration, &mut TranspositionTable::new(1), &mut || false);
+            assert_eq!(result.value, expected.value, "{}", fen);
+        }
+
+        // the value jumps as the runaway pawn's promotion comes into view
+        let board = parse_fen("7k/8/8/8/P7/8/8/K7 w - - 0 1").unwrap();
+        let mut fails = AspirationFails(Vec::new());
+        let result = board.search_with_config(
+            &SearchLimits::depth(6),
+            SearchConfig::default(),
+            &mut TranspositionTable::new(1),
+            &mut fails,
+        );
+        assert_eq!(result.depth, 6);
+        assert!(!fails.0.is_empty());
+        let fail_lows = fails.0.iter().filter(|bound| **bound == Bound::Upper).count() as u64;
+        assert_eq!(result.stats.aspiration_fail_lows, fail_lows);
+        assert_eq!(result.stats.aspiration_fail_highs, fails.0.len() as u64 - fail_lows);
+    }
+
+    /// Records how many lines each iteration reports.
+    struct CountLines(Vec<usize>);
+
+    impl SearchControl for CountLines {
+        fn should_stop(&mut self) -> bool { false }
+
+        fn on_lines(&mut self, lines: &[SearchResult]) -> bool {
+            self.0.push(lines.len());
+            true
+        }
+    }
+
+    #[test]
+    fn test_multipv() {
+        let board = parse_fen(POSITIONS[1]).unwrap();
+        let plain = SearchConfig::plain();
+        let mut counts = CountLines(Vec::new());
+        let lines = board.search_multipv(&SearchLimits::depth(3), plain, 3, &mut TranspositionTable::new(1), &mut counts);
+        assert_eq!(counts.0, [3, 3, 3]);
+        assert_eq!(lines.len(), 3);
+
+        // each line is as good as the move would be on its own
+        let legal_moves = board.get_legal_moves();
+        let mut values = Vec::new();
+        for m in &legal_moves {
+            let others = legal_moves.iter().copied().filter(|other| other != m).collect::<Vec<_>>();
+            let mut table = TranspositionTable::new(1);
+            let mut never_stop = || false;
+            let mut searcher = Searcher::new(&mut table, &mut never_stop, None, plain);
+            values.push(searcher.search_root(&board, 3, -Score::INFINITE, Score::INFINITE, &others).unwrap().1);
+        }
+        values.sort_by_key(|value| Reverse(*value));
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(line.value, values[i]);
+            assert_eq!(line.pv.first(), Some(&line.best_move));
+            assert!(lines[..i].iter().all(|other| other.best_move != line.best_move));
+        }
+
+        // there can't be more lines than legal moves
+        let board = parse_fen("7k/8/6K1/5Q2/8/8/8/8 b - - 0 1").unwrap();
+        let lines = board.search_multipv(&SearchLimits::depth(2), plain, 5, &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(lines.len(), board.get_legal_moves().len());
+        let board = parse_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        let lines = board.search_multipv(&SearchLimits::depth(2), plain, 5, &mut TranspositionTable::new(1), &mut || false);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].best_move, Move::Resign);
+    }
+}
@@ -11,7 +11,7 @@ impl From<Piece> for Square {
 
 impl Square {
     #[inline]
-    pub fn is_empty(&self) -> bool { self.piece == None }
+    pub fn is_empty(&self) -> bool { self.piece.is_none() }
 
     #[inline]
     pub fn get_piece(&self) -> Option<Piece> { self.piece }
@@ -0,0 +1,329 @@
+extern crate chess_engine;
+use chess_engine::*;
+use std::{
+    io::{stdin, BufRead},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const ENGINE_NAME: &str = "rust_chessai";
+const ENGINE_AUTHOR: &str = "jxuanli";
+
+/// Roughly how much longer each iteration takes than the previous one.
+const BRANCHING_FACTOR: u32 = 6;
+
+/// The largest transposition table allowed by the `Hash` option, in megabytes.
+const MAX_HASH_MB: usize = 1024;
+
+/// The most lines the `MultiPV` option can ask for.
+const MAX_MULTIPV: usize = 256;
+
+/// The check options that switch search refinements on and off.
+const SEARCH_OPTIONS: [&str; 6] = ["MoveOrdering", "PVS", "NullMove", "LMR", "CheckExtensions", "AspirationWindows"];
+
+/// The refinement switched by one of the `SEARCH_OPTIONS`.
+fn search_option<'a>(config: &'a mut SearchConfig, name: &str) -> Option<&'a mut bool> {
+    match name.to_ascii_lowercase().as_str() {
+        "moveordering" => Some(&mut config.move_ordering),
+        "pvs" => Some(&mut config.pvs),
+        "nullmove" => Some(&mut config.null_move),
+        "lmr" => Some(&mut config.late_move_reductions),
+        "checkextensions" => Some(&mut config.check_extensions),
+        "aspirationwindows" => Some(&mut config.aspiration_windows),
+        _ => None,
+    }
+}
+
+/// Parse the arguments of a `go` command into search limits.
+fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|v| v.parse::<u64>().ok());
+        match *arg {
+            "depth" => limits.depth = value().map(|d| d as i32),
+            "nodes" => limits.nodes = value(),
+            "movetime" => limits.movetime = value(),
+            "wtime" => limits.wtime = value(),
+            "btime" => limits.btime = value(),
+            "winc" => limits.winc = value().unwrap_or(0),
+            "binc" => limits.binc = value().unwrap_or(0),
+            "infinite" => limits.infinite = true,
+            _ => {}
+        }
+    }
+    limits
+}
+
+/// Parse the arguments of a `position` command into a board.
+fn parse_position(args: &[&str]) -> Result<Board, String> {
+    let moves_at = args.iter().position(|arg| *arg == "moves");
+    let (setup, moves) = match moves_at {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut board = match setup.split_first() {
+        Some((&"startpos", _)) => Board::default(),
+        Some((&"fen", fen)) => parse_fen(&fen.join(" "))?,
+        _ => return Err(String::from("expected `startpos` or `fen`")),
+    };
+
+    for s in moves {
+        let m = Move::from_uci(&board, s)?;
+        match board.play_move(m) {
+            GameResult::IllegalMove(_) => return Err(format!("illegal move `{}`", s)),
+            // keep the board even when the move ends the game
+            _ => board = board.apply_eval_move(m),
+        }
+    }
+    Ok(board)
+}
+
+/// Write a score as `cp <centipawns>`, or `mate <moves>` for a forced mate.
+fn format_score(score: Score) -> String {
+    match score.get_mate() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score.get_centipawns()),
+    }
+}
+
+/// Write a line of moves from the given board in UCI notation.
+fn format_pv(board: &Board, pv: &[Move]) -> String {
+    let mut board = *board;
+    let mut moves = Vec::new();
+    for m in pv {
+        moves.push(m.to_uci(&board));
+        board = board.apply_eval_move(*m);
+    }
+    moves.join(" ")
+}
+
+/// Stops the search on `stop` or when the time runs out, and reports
+/// each completed iteration and each aspiration window failure.
+struct UciControl<'a> {
+    board: &'a Board,
+    /// How many lines are searched, from the `MultiPV` option.
+    lines: usize,
+    start: Instant,
+    budget: Option<Duration>,
+    iteration_start: Instant,
+    stop: &'a AtomicBool,
+}
+
+impl UciControl<'_> {
+    /// Print an `info` line for the given line of a search result,
+    /// counting from one, marking its score as a bound if it is only one.
+    fn print_info(&self, result: &SearchResult, line: usize, bound: Bound) {
+        let elapsed = self.start.elapsed();
+        let nodes = result.stats.board_count;
+        let bound = match bound {
+            Bound::Exact => "",
+            Bound::Lower => " lowerbound",
+            Bound::Upper => " upperbound",
+        };
+        println!(
+            "info depth {} seldepth {} multipv {} score {}{} nodes {} nps {} time {} pv {}",
+            result.depth,
+            result.stats.seldepth,
+            line,
+            format_score(result.value),
+            bound,
+            nodes,
+            (nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+            elapsed.as_millis(),
+            format_pv(self.board, &result.pv)
+        );
+    }
+}
+
+impl SearchControl for UciControl<'_> {
+    fn should_stop(&mut self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.budget.is_some_and(|budget| self.start.elapsed() >= budget)
+    }
+
+    fn on_lines(&mut self, lines: &[SearchResult]) -> bool {
+        if lines[0].best_move == Move::Resign {
+            // no legal moves to choose from
+            return false;
+        }
+
+        for (i, result) in lines.iter().enumerate() {
+            self.print_info(result, i + 1, Bound::Exact);
+        }
+
+        // don't start an iteration that can't finish in time
+        let iteration_time = self.iteration_start.elapsed();
+        self.iteration_start = Instant::now();
+        !self.should_stop()
+            && self
+                .budget
+                .is_none_or(|budget| self.start.elapsed() + iteration_time * BRANCHING_FACTOR <= budget)
+    }
+
+    fn on_aspiration_fail(&mut self, result: &SearchResult, bound: Bound) {
+        // with several lines, there's no telling which one failed
+        if self.lines == 1 {
+            self.print_info(result, 1, bound);
+        }
+    }
+}
+
+/// Search the board until a limit is reached, then report the best move
+/// found.
+fn search(
+    board: Board,
+    limits: SearchLimits,
+    config: SearchConfig,
+    lines: usize,
+    table: &mut TranspositionTable,
+    stop: Arc<AtomicBool>,
+) {
+    let mut control = UciControl {
+        board: &board,
+        lines,
+        start: Instant::now(),
+        budget: limits.time_budget(board.get_turn_color()).map(Duration::from_millis),
+        iteration_start: Instant::now(),
+        stop: &stop,
+    };
+    let result = board.search_multipv(&limits, config, lines, table, &mut control).remove(0);
+    println!("info hashfull {}", table.hashfull());
+
+    // `go infinite` must not report a move until told to stop
+    while limits.infinite && !stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    match result.best_move {
+        Move::Resign => println!("bestmove 0000"),
+        m => println!("bestmove {}", m.to_uci(&board)),
+    }
+}
+
+/// Parse the arguments of a `setoption` command into a name and value.
+fn parse_option(args: &[&str]) -> Option<(String, String)> {
+    let name_at = args.iter().position(|arg| *arg == "name")?;
+    let value_at = args.iter().position(|arg| *arg == "value");
+    let name = args[name_at + 1..value_at.unwrap_or(args.len())].join(" ");
+    let value = value_at.map(|i| args[i + 1..].join(" ")).unwrap_or_default();
+    Some((name, value))
+}
+
+fn stop_search(searching: &mut Option<JoinHandle<()>>, stop: &AtomicBool) {
+    if let Some(handle) = searching.take() {
+        stop.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+    }
+}
+
+fn main() {
+    let mut board = Board::default();
+    let mut searching: Option<JoinHandle<()>> = None;
+    let stop = Arc::new(AtomicBool::new(false));
+    let table = Arc::new(Mutex::new(TranspositionTable::default()));
+    let mut config = SearchConfig::default();
+    let mut lines = 1;
+
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+
+        match command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_TABLE_SIZE_MB, MAX_HASH_MB
+                );
+                println!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV);
+                for name in SEARCH_OPTIONS {
+                    println!("option name {} type check default true", name);
+                }
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                stop_search(&mut searching, &stop);
+                board = Board::default();
+                table.lock().unwrap().clear();
+            }
+            "setoption" => {
+                stop_search(&mut searching, &stop);
+                match parse_option(args) {
+                    Some((name, value)) if name.eq_ignore_ascii_case("hash") => {
+                        match value.parse::<usize>() {
+                            Ok(size_mb) => {
+                                *table.lock().unwrap() =
+                                    TranspositionTable::new(size_mb.clamp(1, MAX_HASH_MB))
+                            }
+                            Err(_) => println!("info string invalid hash size `{}`", value),
+                        }
+                    }
+                    Some((name, value)) if name.eq_ignore_ascii_case("multipv") => match value.parse::<usize>() {
+                        Ok(value) => lines = value.clamp(1, MAX_MULTIPV),
+                        Err(_) => println!("info string invalid number of lines `{}`", value),
+                    },
+                    Some((name, value)) => match search_option(&mut config, &name) {
+                        Some(enabled) => match value.parse::<bool>() {
+                            Ok(value) => *enabled = value,
+                            Err(_) => println!("info string expected `true` or `false` for `{}`", name),
+                        },
+                        None => println!("info string unknown option `{}`", name),
+                    },
+                    None => println!("info string expected `name`"),
+                }
+            }
+            "position" => {
+                stop_search(&mut searching, &stop);
+                match parse_position(args) {
+                    Ok(next_board) => board = next_board,
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            "go" => {
+                stop_search(&mut searching, &stop);
+                stop.store(false, Ordering::Relaxed);
+                let limits = parse_go(args);
+                let stop = Arc::clone(&stop);
+                let table = Arc::clone(&table);
+                searching = Some(thread::spawn(move || {
+                    search(board, limits, config, lines, &mut table.lock().unwrap(), stop)
+                }));
+            }
+            "stop" => stop_search(&mut searching, &stop),
+            "quit" => break,
+            _ => println!("info string unknown command `{}`", command),
+        }
+    }
+
+    stop_search(&mut searching, &stop);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_position() {
+        let board = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        assert_eq!(format_fen(&board).unwrap(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+
+        // a null move would otherwise be played as a resignation
+        assert!(parse_position(&["startpos", "moves", "e2e4", "0000"]).is_err());
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_err());
+        assert!(parse_position(&["moves", "e2e4"]).is_err());
+    }
+}
@@ -6,19 +6,15 @@ use crate::piece::Piece;
 use crate::position::Position;
 use crate::{Color, Evaluate, Move};
 
-pub fn format_fen(
-    board: &Board,
-    halfmove_clock: u8,
-    fullmove_number: u8,
-) -> Result<String, String> {
+pub fn format_fen(board: &Board) -> Result<String, String> {
     let mut fen: Vec<String> = vec![];
 
     placement(board, &mut fen);
     active_color(&mut fen, board);
     castling(&mut fen, board);
     en_passant(&mut fen, board);
-    half_move(&mut fen, halfmove_clock);
-    full_move(&mut fen, fullmove_number);
+    half_move(&mut fen, board.get_halfmove_clock());
+    full_move(&mut fen, board.get_fullmove_number());
 
     Ok(fen.join(""))
 }
@@ -113,13 +109,13 @@ fn en_passant(fen: &mut Vec<String>, board: &Board) {
     });
 }
 
-fn half_move(fen: &mut Vec<String>, halfmove_clock: u8) {
+fn half_move(fen: &mut Vec<String>, halfmove_clock: u32) {
     fen.push(" ".to_string());
     let halfmove_clock = halfmove_clock.to_string();
     fen.push(halfmove_clock);
 }
 
-fn full_move(fen: &mut Vec<String>, fullmove_number: u8) {
+fn full_move(fen: &mut Vec<String>, fullmove_number: u32) {
     fen.push(" ".to_string());
     let fullmove_number = fullmove_number.to_string();
     fen.push(fullmove_number);
@@ -132,8 +128,8 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
     let active_color = parts.next();
     let castling = parts.next();
     let en_passant = parts.next();
-    let _halfmove_clock = parts.next();
-    let _fullmove_number = parts.next();
+    let halfmove_clock = parts.next();
+    let fullmove_number = parts.next();
     // make sure all parts present
     if placement.is_none()
         || active_color.is_none()
@@ -160,7 +156,7 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
                     false => Color::Black,
                 };
                 // using as because row/col always in u8 range
-                let position = Position::new(row as i32, col as i32);
+                let position = Position::new(row, col);
                 let piece = match x.to_ascii_lowercase() {
                     'b' => Piece::Bishop(color, position),
                     'n' => Piece::Knight(color, position),
@@ -235,16 +231,37 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
         },
     });
 
+    if let Some(halfmove_clock) = halfmove_clock {
+        builder = builder.set_halfmove_clock(match halfmove_clock.parse() {
+            Ok(halfmove_clock) => halfmove_clock,
+            Err(_) => {
+                return Err(String::from("invalid halfmove clock"));
+            }
+        });
+    }
+
+    if let Some(fullmove_number) = fullmove_number {
+        builder = builder.set_fullmove_number(match fullmove_number.parse() {
+            Ok(fullmove_number) if fullmove_number > 0 => fullmove_number,
+            _ => {
+                return Err(String::from("invalid fullmove number"));
+            }
+        });
+    }
+
     Ok(builder.build())
 }
 
 pub fn parse_san_move(board: &Board, move_str: &str) -> Result<Move, String> {
-    if move_str == "0-0" {
+    // check, checkmate and annotation suffixes don't change the move
+    let move_str = move_str.trim_end_matches(['+', '#', '!', '?']);
+    if move_str == "0-0" || move_str == "O-O" {
         return Ok(Move::KingSideCastle {});
-    } else if move_str == "0-0-0" {
+    } else if move_str == "0-0-0" || move_str == "O-O-O" {
         return Ok(Move::QueenSideCastle {});
     }
 
+    let move_str = move_str.replace('=', "");
     let mut chars = move_str.chars();
 
     // optional pawn promotion
@@ -273,15 +290,14 @@ pub fn parse_san_move(board: &Board, move_str: &str) -> Result<Move, String> {
 }
 
 fn compute_move_promotion(last: Option<char>, color: Color, offboard: Position) -> Option<Piece> {
-    let move_promotion = match last {
+    match last {
         Some('Q') => Some(Piece::Queen(color, offboard)),
         Some('K') => Some(Piece::King(color, offboard)),
         Some('N') => Some(Piece::Knight(color, offboard)),
         Some('B') => Some(Piece::Bishop(color, offboard)),
         Some('R') => Some(Piece::Rook(color, offboard)),
         _ => None,
-    };
-    move_promotion
+    }
 }
 
 fn compute_move_to(to: String) -> Result<Position, Result<Move, String>> {
@@ -295,19 +311,18 @@ fn compute_move_to(to: String) -> Result<Position, Result<Move, String>> {
 }
 
 fn compute_piece(source_column: Option<char>, color: Color, offboard: Position) -> Piece {
-    let piece = match source_column {
+    match source_column {
         Some('B') => Piece::Bishop(color, offboard),
         Some('K') => Piece::King(color, offboard),
         Some('N') => Piece::Knight(color, offboard),
         Some('Q') => Piece::Queen(color, offboard),
         Some('R') => Piece::Rook(color, offboard),
         _ => Piece::Pawn(color, offboard),
-    };
-    piece
+    }
 }
 
 fn parse_source_column(source_column: Option<char>) -> Option<i32> {
-    let column = match source_column {
+    match source_column {
         Some('a') => Some(0),
         Some('b') => Some(1),
         Some('c') => Some(2),
@@ -317,12 +332,11 @@ fn parse_source_column(source_column: Option<char>) -> Option<i32> {
         Some('g') => Some(6),
         Some('h') => Some(7),
         _ => None,
-    };
-    column
+    }
 }
 
 fn parse_source_row(source_row: Option<char>) -> Option<i32> {
-    let row = match source_row {
+    match source_row {
         Some('1') => Some(0),
         Some('2') => Some(1),
         Some('3') => Some(2),
@@ -332,13 +346,12 @@ fn parse_source_row(source_row: Option<char>) -> Option<i32> {
         Some('7') => Some(6),
         Some('8') => Some(7),
         _ => None,
-    };
-    row
+    }
 }
 
 fn do_legal_moves(board: &Board, move_to: Position, piece: Piece, column: Option<i32>, row: Option<i32>, candidates: &mut Vec<Piece>) {
     for legal_move in board.get_legal_moves() {
-        if let Move::Piece(from, to) = legal_move {
+        if let Move::Piece(from, to) | Move::Promotion(from, to, _) = legal_move {
             if move_to == to {
                 if let Some(board_piece) = board.get_piece(from) {
                     // filter based on type
@@ -346,6 +359,8 @@ fn do_legal_moves(board: &Board, move_to: Position, piece: Piece, column: Option
                     if board_piece.get_name() == piece.get_name()
                         && (column.is_none() || column == Some(pos.get_col()))
                         && (row.is_none() || row == Some(pos.get_row()))
+                        // each promotion is a separate move from the same pawn
+                        && !candidates.contains(&board_piece)
                     {
                         candidates.push(board_piece);
                     }
@@ -369,6 +384,98 @@ fn match_candidates(candidates: Vec<Piece>, move_promotion: Option<Piece>, move_
     }
 }
 
+/// Format a legal move in standard algebraic notation, e.g. `Nbd7`, `exd6`,
+/// `e8=Q+` or `O-O-O#`. A pawn move onto the last rank without an explicit
+/// promotion is written as a queen promotion.
+pub fn to_san(board: &Board, m: Move) -> String {
+    let mut san = match m {
+        Move::KingSideCastle => "O-O".to_string(),
+        Move::QueenSideCastle => "O-O-O".to_string(),
+        Move::Resign => return "resign".to_string(),
+        Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+            let piece = match board.get_piece(from) {
+                Some(piece) => piece,
+                None => return m.to_string(),
+            };
+            let is_capture = board.has_enemy_piece(to, piece.get_color())
+                || piece.is_pawn() && from.get_col() != to.get_col();
+
+            let mut san = String::new();
+            if piece.is_pawn() {
+                if is_capture {
+                    san.push(file_char(from));
+                }
+            } else {
+                san.push(piece_char(piece));
+                san.push_str(&disambiguation(board, piece, to));
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&to.to_string());
+
+            if piece.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
+                san.push('=');
+                san.push(match m {
+                    Move::Promotion(_, _, promotion) => piece_char(promotion),
+                    _ => 'Q',
+                });
+            }
+            san
+        }
+    };
+
+    let next_board = board.apply_eval_move(m);
+    if next_board.is_checkmate() {
+        san.push('#');
+    } else if next_board.is_in_check(next_board.get_turn_color()) {
+        san.push('+');
+    }
+    san
+}
+
+fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::King(_, _) => 'K',
+        Piece::Queen(_, _) => 'Q',
+        Piece::Rook(_, _) => 'R',
+        Piece::Bishop(_, _) => 'B',
+        Piece::Knight(_, _) => 'N',
+        Piece::Pawn(_, _) => 'P',
+    }
+}
+
+fn file_char(pos: Position) -> char {
+    (b'a' + pos.get_col() as u8) as char
+}
+
+/// The shortest prefix (file, rank or both) that tells `piece` apart from
+/// other pieces of the same kind that can also move to `to`.
+fn disambiguation(board: &Board, piece: Piece, to: Position) -> String {
+    let from = piece.get_pos();
+    let rivals = board
+        .get_legal_moves()
+        .into_iter()
+        .filter_map(|m| match m {
+            Move::Piece(other, other_to) if other_to == to && other != from => {
+                board.get_piece(other)
+            }
+            _ => None,
+        })
+        .filter(|other| other.get_name() == piece.get_name())
+        .collect::<Vec<Piece>>();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|other| other.get_pos().get_col() != from.get_col()) {
+        file_char(from).to_string()
+    } else if rivals.iter().all(|other| other.get_pos().get_row() != from.get_row()) {
+        (from.get_row() + 1).to_string()
+    } else {
+        from.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::string::{String, ToString};
@@ -383,17 +490,17 @@ mod tests {
         let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
         let board = Board::default();
-        assert_eq!(format_fen(&board, 0, 1).unwrap(), String::from(start));
+        assert_eq!(format_fen(&board).unwrap(), String::from(start));
 
         let board = parse_fen(start).unwrap();
-        assert_eq!(format_fen(&board, 0, 1).unwrap(), String::from(start));
+        assert_eq!(format_fen(&board).unwrap(), String::from(start));
 
         let board = match board.play_move(Move::Piece(E2, E4)) {
             GameResult::Continuing(board) => board,
             _ => panic!("e4 failed"),
         };
         assert_eq!(
-            format_fen(&board, 0, 1).unwrap(),
+            format_fen(&board).unwrap(),
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string()
         );
 
@@ -402,7 +509,7 @@ mod tests {
             _ => panic!("c5 failed"),
         };
         assert_eq!(
-            format_fen(&board, 0, 2).unwrap(),
+            format_fen(&board).unwrap(),
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2".to_string()
         );
 
@@ -411,9 +518,28 @@ mod tests {
             _ => panic!("Nf3 failed"),
         };
         assert_eq!(
-            format_fen(&board, 1, 2).unwrap(),
+            format_fen(&board).unwrap(),
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2".to_string()
-        )
+        );
+
+        let board = match board.play_move(Move::Piece(B8, C6)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("Nc6 failed"),
+        };
+        assert_eq!(
+            format_fen(&board).unwrap(),
+            "r1bqkbnr/pp1ppppp/2n5/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3".to_string()
+        );
+
+        let midgame = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 12 34";
+        assert_eq!(format_fen(&parse_fen(midgame).unwrap()).unwrap(), midgame);
+
+        // the move counters are optional
+        let board = parse_fen("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!(board.get_halfmove_clock(), 0);
+        assert_eq!(board.get_fullmove_number(), 1);
+        assert!(parse_fen("8/8/8/8/8/8/8/K6k w - - x 1").is_err());
+        assert!(parse_fen("8/8/8/8/8/8/8/K6k w - - 0 0").is_err());
     }
 
     #[test]
@@ -451,4 +577,73 @@ mod tests {
             "no matching move".to_string()
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_to_san() {
+        let board = Board::default();
+        assert_eq!(to_san(&board, Move::Piece(E2, E4)), "e4");
+        assert_eq!(to_san(&board, Move::Piece(G1, F3)), "Nf3");
+
+        // disambiguation by file, rank and both
+        let board = parse_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(B1, D2)), "Nbd2");
+        let board = parse_fen("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(A1, A3)), "R1a3");
+        let board = parse_fen("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(A1, B2)), "Qa1b2");
+
+        // en passant
+        let board =
+            parse_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(E5, D6)), "exd6");
+
+        // promotions
+        let board = parse_fen("1n5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(A7, A8)), "a8=Q");
+        assert_eq!(
+            to_san(&board, Move::Promotion(A7, B8, Piece::Knight(Color::White, B8))),
+            "axb8=N"
+        );
+
+        // castling, check and checkmate
+        let board = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::KingSideCastle), "O-O");
+        assert_eq!(to_san(&board, Move::QueenSideCastle), "O-O-O");
+        let board = parse_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::KingSideCastle), "O-O+");
+        let board = parse_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(A1, A8)), "Ra8+");
+        let board =
+            parse_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2").unwrap();
+        assert_eq!(to_san(&board, Move::Piece(D8, H4)), "Qh4#");
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1",
+        ];
+        for fen in &positions {
+            let board = parse_fen(fen).unwrap();
+            for m in board.get_legal_moves() {
+                let san = to_san(&board, m);
+                let parsed = parse_san_move(&board, &san)
+                    .unwrap_or_else(|e| panic!("{} in {}: {}", san, fen, e));
+                assert_eq!(
+                    board.apply_eval_move(parsed),
+                    board.apply_eval_move(m),
+                    "{} in {}",
+                    san,
+                    fen
+                );
+            }
+        }
+    }
+}
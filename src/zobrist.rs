@@ -0,0 +1,175 @@
+use super::{Color, Piece, Position};
+
+/// Random keys for Zobrist hashing, generated at compile time so the
+/// hashes are the same on every run.
+pub(crate) struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant: [u64; 8],
+}
+
+pub(crate) static KEYS: ZobristKeys = ZobristKeys::generate(0x5EED_C4E5_5A1B_0A2D);
+
+/// Advance a splitmix64 generator, returning the new state and its output.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+impl ZobristKeys {
+    const fn generate(seed: u64) -> Self {
+        let mut state = seed;
+        let mut key;
+
+        let mut pieces = [[0; 64]; 12];
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                (state, key) = splitmix64(state);
+                pieces[piece][square] = key;
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        (state, key) = splitmix64(state);
+        let black_to_move = key;
+
+        let mut castling = [0; 4];
+        let mut i = 0;
+        while i < 4 {
+            (state, key) = splitmix64(state);
+            castling[i] = key;
+            i += 1;
+        }
+
+        let mut en_passant = [0; 8];
+        let mut i = 0;
+        while i < 8 {
+            (state, key) = splitmix64(state);
+            en_passant[i] = key;
+            i += 1;
+        }
+
+        Self {
+            pieces,
+            black_to_move,
+            castling,
+            en_passant,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn piece(&self, piece: Piece) -> u64 {
+        let kind = piece.kind_index();
+        let color = match piece.get_color() {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+        let pos = piece.get_pos();
+        self.pieces[kind * 2 + color][(pos.get_row() * 8 + pos.get_col()) as usize]
+    }
+
+    #[inline]
+    pub(crate) fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+
+    #[inline]
+    pub(crate) fn castling(&self, color: Color, kingside: bool) -> u64 {
+        match (color, kingside) {
+            (Color::White, true) => self.castling[0],
+            (Color::White, false) => self.castling[1],
+            (Color::Black, true) => self.castling[2],
+            (Color::Black, false) => self.castling[3],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn en_passant(&self, pos: Position) -> u64 {
+        self.en_passant[pos.get_col() as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_fen, Board, Evaluate, Move};
+
+    /// A small xorshift generator so playouts are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_incremental_hash() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let starts = [
+            Board::default(),
+            parse_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap(),
+            parse_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(),
+        ];
+
+        for playout in 0..2000 {
+            let mut board = starts[playout % starts.len()];
+            assert_eq!(board.hash(), board.compute_hash());
+            for _ in 0..40 {
+                let moves = board.get_legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                let m = moves[rng.next() as usize % moves.len()];
+                board = board.apply_eval_move(m);
+                assert_eq!(board.hash(), board.compute_hash(), "after {}", m);
+                assert_eq!(board.pawn_hash(), board.compute_pawn_hash(), "after {}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_identifies_position() {
+        let board = Board::default();
+        let transposed = [
+            Move::Piece(crate::G1, crate::F3),
+            Move::Piece(crate::G8, crate::F6),
+            Move::Piece(crate::B1, crate::C3),
+        ]
+        .iter()
+        .fold(board, |board, m| board.apply_eval_move(*m));
+        let original = [
+            Move::Piece(crate::B1, crate::C3),
+            Move::Piece(crate::G8, crate::F6),
+            Move::Piece(crate::G1, crate::F3),
+        ]
+        .iter()
+        .fold(board, |board, m| board.apply_eval_move(*m));
+        assert_eq!(transposed.hash(), original.hash());
+
+        assert_ne!(board.hash(), board.change_turn().hash());
+        assert_ne!(
+            parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap().hash(),
+            parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap().hash()
+        );
+        // an en passant square only matters when a pawn can capture on it
+        assert_ne!(
+            parse_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap().hash(),
+            parse_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap().hash()
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap().hash(),
+            parse_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap().hash()
+        );
+    }
+}